@@ -14,231 +14,318 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+// Evaluates queries against the parsed GPOs and returns the findings. Writing the
+// "analysis/*.txt" reports is left to the caller.
+
+use core::fmt;
 use crate::gpo::GroupPolicy;
-use std::fs::{self, ReadDir};
-use std::
-    path::{Path, PathBuf};
+use crate::pki::Certificate;
+use crate::query::{Query, Severity};
+use chrono::NaiveDateTime;
+use serde::{Serialize, Serializer};
+use std::path::{Path, PathBuf};
 use anyhow::{Result, anyhow};
-use std::io::BufReader;
 use std::io::prelude::*;
 use std::fs::File;
 
-pub fn analyze(gpos: &Vec<GroupPolicy>, output_path: &PathBuf) -> Result<()>{
-    let d_analysis_file_buf = output_path.join("desireables.txt");
-    let d_analysis_file = Path::new(&d_analysis_file_buf);
-    if d_analysis_file.exists() {
-        std::fs::remove_file(d_analysis_file)?;
-    } 
-    let mut d_file = File::create(d_analysis_file)?;
-
-    let u_analysis_file_buf = output_path.join("undesireables.txt");
-    let u_analysis_file = Path::new(&u_analysis_file_buf);
-    if u_analysis_file.exists() {
-        std::fs::remove_file(u_analysis_file)?;
-    } 
-    let mut u_file = File::create(u_analysis_file)?;
-
-    let w_analysis_file_buf = output_path.join("warnings.txt");
-    let w_analysis_file = Path::new(&w_analysis_file_buf);
-    if w_analysis_file.exists() {
-        std::fs::remove_file(w_analysis_file)?;
-    } 
-    let mut w_file = File::create(w_analysis_file)?;
-
-    let m_analysis_file_buf = output_path.join("missing.txt");
-    let m_analysis_file = Path::new(&m_analysis_file_buf);
-    if m_analysis_file.exists() {
-        std::fs::remove_file(m_analysis_file)?;
-    } 
-    let mut m_file = File::create(m_analysis_file)?;
-
-
-
-    let current_dir_buf = std::env::current_dir()?;
-    let current_query_files_buf = current_dir_buf.join("queries");
-    let current_query_files_path = Path::new(&current_query_files_buf);
+// The report files written by write_reports, in the order they are printed.
+const REPORTS: &[(Severity, &str)] = &[
+    (Severity::Undesirable, "undesireables.txt"),
+    (Severity::Desirable, "desireables.txt"),
+    (Severity::Warning, "warnings.txt"),
+    (Severity::Missing, "missing.txt"),
+];
+
+// Findings refer to GPOs by name. The full GPOs are already written to "gpos.json".
+fn serialize_gpo_name<S: Serializer>(gpo: &&GroupPolicy, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&gpo.name)
+}
+
+fn serialize_optional_gpo_name<S: Serializer>(gpo: &Option<&GroupPolicy>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(gpo.map(|gpo| gpo.name.as_str()).unwrap_or_default())
+}
+
+// A query that matched a GPO, or a "missing" query that matched none.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding<'a> {
+    pub query: &'a Query,
+    #[serde(serialize_with = "serialize_optional_gpo_name")]
+    pub gpo: Option<&'a GroupPolicy>,
+    pub matching: Vec<String>,
+}
+
+impl fmt::Display for Finding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.query.source.display())?;
+        if !self.query.category.is_empty() {
+            write!(f, " | Category: {}", self.query.category)?;
+        }
+        if let Some(rule) = &self.query.rule {
+            write!(f, "\n{}", rule)?;
+        }
+        match self.gpo {
+            Some(gpo) => write!(f, "\n{}\n\t{}\n\tMatching Value(s):\n\t{}", gpo, self.query, self.matching.join("\n\t").trim_end()),
+            None => write!(f, "\n\t{}", self.query),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateFinding<'a> {
+    pub reasons: Vec<&'static str>,
+    #[serde(serialize_with = "serialize_gpo_name")]
+    pub gpo: &'a GroupPolicy,
+    pub certificate: &'a Certificate,
+}
+
+impl fmt::Display for CertificateFinding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n{}\n\t{}\n\tPurposes: {}", self.reasons.join(", "), self.gpo, self.certificate, self.certificate.purposes)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Analysis<'a> {
+    pub findings: Vec<Finding<'a>>,
+    pub certificates: Vec<CertificateFinding<'a>>,
+}
+
+impl<'a> Analysis<'a> {
+    pub fn findings_of(&self, severity: Severity) -> impl Iterator<Item = &Finding<'a>> {
+        self.findings.iter().filter(move |finding| finding.query.severity == severity)
+    }
+
+    // The text of one report, i.e. "Undesirables" followed by every undesirable finding.
+    pub fn report(&self, severity: Severity) -> String {
+        let findings: String = self.findings_of(severity).map(|finding| format!("{}\n\n", finding)).collect();
+        if findings.is_empty() {
+            return findings;
+        }
+        format!("{}\n{}", severity, findings)
+    }
+
+    pub fn certificate_report(&self) -> String {
+        let findings: String = self.certificates.iter().map(|finding| format!("{}\n\n", finding)).collect();
+        if findings.is_empty() {
+            return findings;
+        }
+        format!("Certificates\n{}", findings)
+    }
+
+    // Every non-empty report in the order they are printed.
+    pub fn reports(&self) -> Vec<String> {
+        let mut reports: Vec<String> = REPORTS.iter().map(|(severity, _)| self.report(*severity)).collect();
+        reports.push(self.certificate_report());
+        reports.retain(|report| !report.is_empty());
+        reports
+    }
+}
+
+pub fn analyze<'a>(gpos: &'a [GroupPolicy], queries: &'a [Query], now: &NaiveDateTime) -> Analysis<'a> {
+    let mut analysis = Analysis::default();
+    for query in queries.iter() {
+        if query.severity == Severity::Missing {
+            if !gpos.iter().any(|policy| query.matches(policy, now)) {
+                analysis.findings.push(Finding { query, gpo: None, matching: Vec::new() });
+            }
+            continue;
+        }
+        for policy in gpos.iter().filter(|policy| query.matches(policy, now)) {
+            analysis.findings.push(Finding { query, gpo: Some(policy), matching: query.matching_values(policy, now) });
+        }
+    }
+    analysis.certificates = certificate_findings(gpos, now);
+    analysis
+}
+
+// The "queries" directory next to the executable, or else in the current working directory.
+pub fn queries_dir() -> Result<PathBuf> {
+    let current_query_files_path = std::env::current_dir()?.join("queries");
     let exe_buf = std::env::current_exe()?;
     let exe_parent_buf = exe_buf.parent().ok_or(anyhow!("No parent directory could be determined for the current executable path."))?;
-    let exe_files_buf = exe_parent_buf.join("queries");
-    let exe_query_files_path = Path::new(&exe_files_buf);
-    let query_files : ReadDir;
-
-    #[cfg(debug_assertions)]
-    println!("Queries search logic -- Debug:");
-    #[cfg(debug_assertions)]
-    println!("Current Directory: {} Exists: {}", current_query_files_path.display(), current_query_files_path.exists());
-    #[cfg(debug_assertions)]
-    println!("Current Directory: {} Exists: {}", exe_query_files_path.display(), exe_query_files_path.exists());
-
-    if !current_query_files_path.exists() && !exe_query_files_path.exists() { return Err(anyhow!("The queries directory could not be found.")); }
+    let exe_query_files_path = exe_parent_buf.join("queries");
+
     if exe_query_files_path.exists() {
-        query_files = fs::read_dir(&exe_query_files_path)?;
+        Ok(exe_query_files_path)
+    } else if current_query_files_path.exists() {
+        Ok(current_query_files_path)
     } else {
-        query_files = fs::read_dir(&current_query_files_path)?;
+        Err(anyhow!("The queries directory could not be found."))
     }
+}
 
-    let mut undesirables = String::new();
-    let mut warnings = String::new();
-    let mut desirables = String::new();
-    let mut missings = String::new();
+// Write every report into the analysis directory. Reports without findings are left empty.
+pub fn write_reports(analysis: &Analysis, output_path: &Path) -> Result<()> {
+    for (severity, file_name) in REPORTS.iter() {
+        let mut file = File::create(output_path.join(file_name))?;
+        file.write_all(analysis.report(*severity).as_bytes())?;
+    }
+    let mut file = File::create(output_path.join("certificates.txt"))?;
+    file.write_all(analysis.certificate_report().as_bytes())?;
+    Ok(())
+}
 
-    for query_file in query_files {
-        let query_path = match query_file {
-            Ok(v) => {
-                v.path()
+// Built-in check that doesn't need a query file: every non-Microsoft or unverified root CA,
+// every expired certificate and every EFS recovery agent deployed through Public Key Policies.
+pub fn certificate_findings<'a>(gpos: &'a [GroupPolicy], now: &NaiveDateTime) -> Vec<CertificateFinding<'a>> {
+    let mut findings: Vec<CertificateFinding> = Vec::new();
+    for policy in gpos.iter() {
+        for certificate in policy.public_key_policies.certificates.iter() {
+            let mut reasons: Vec<&str> = Vec::new();
+            if certificate.is_root() && !certificate.is_microsoft() {
+                if certificate.thumbprint.is_empty() && certificate.has_microsoft_name() {
+                    reasons.push("Unverified root CA (no thumbprint in report)");
+                } else {
+                    reasons.push("Non-Microsoft root CA");
+                }
             }
-            Err(e) => {
-                return Err(anyhow!(e));
+            if certificate.is_expired(now) {
+                reasons.push("Expired certificate");
             }
-        };
-        let qf = File::open(&query_path)?;
-        let qf = BufReader::new(qf);
-        for line in qf.lines() {
-            match line {
-                Err(e) => {
-                    println!("The following error was thrown while trying to read the file {}.\n{}", &query_path.display(), e)
-                }
-                Ok(v) => {
-                    let line_raw: Vec<&str> = v.split("--").collect();
-                    if v.is_empty() {
-                        ()
-                    } else if line_raw.len() == 2  && line_raw[0].to_lowercase().trim().starts_with("u") {
-                        let line_queries_raw = line_raw[1].split("|");
-
-                        for policy in gpos.iter() {
-                            let mut query_failed = false;
-                            for line_query in line_queries_raw.clone() {
-                                if !policy.query_gpo(&line_query.trim_start().trim_end()){
-                                    query_failed = true;
-                                    break
-                                }
-                            }
-                            if !query_failed {
-                                let mut matching = String::new();
-                                let full_query_strings: Vec<&str> = v.split("--").collect();
-                                let query_strings = full_query_strings[1].split("|");
-                                let mut output_query_string = "Query Condition(s):\n\t".to_string();
-                                for query in query_strings {
-                                    output_query_string += query.trim_start().trim_end();
-                                    output_query_string += "\n\t";
-                                }
-                                for line_query in line_queries_raw.clone() {
-                                    let matching_string = &policy.get_matching_conditions(&line_query.trim_start().trim_end());
-                                    if !matching_string.is_empty() && !matching.contains(matching_string) {
-                                        matching.push_str(matching_string);
-                                        matching.push_str("\n\t")
-                                    }
-                                }
-                                undesirables += &format!("{}\n{}\n\t{}\n\tMatching Value(s):\n\t{}\n\n", query_path.display(), policy, output_query_string.trim_end(), matching.trim_end());
-                            }
-                        }
-                    } else if line_raw.len() == 2  &&  line_raw[0].to_lowercase().trim().starts_with("d") {
-                        let line_queries_raw = line_raw[1].split("|");
-
-                        for policy in gpos.iter() {
-                            let mut query_failed = false;
-                            for line_query in line_queries_raw.clone() {
-                                if !policy.query_gpo(&line_query.trim_start().trim_end()){
-                                    query_failed = true;
-                                    break
-                                }
-                            }
-                            if !query_failed {
-                                let mut matching = String::new();
-                                let full_query_strings: Vec<&str> = v.split("--").collect();
-                                let query_strings = full_query_strings[1].split("|");
-                                let mut output_query_string = "Query Condition(s):\n\t".to_string();
-                                for query in query_strings {
-                                    output_query_string += query.trim_start().trim_end();
-                                    output_query_string += "\n\t";
-                                }
-                                for line_query in line_queries_raw.clone() {
-                                    let matching_string = &policy.get_matching_conditions(&line_query.trim_start().trim_end());
-                                    if !matching_string.is_empty() && !matching.contains(matching_string) {
-                                        matching.push_str(matching_string);
-                                        matching.push_str("\n\t")
-                                    }
-                                }
-                                desirables += &format!("{}\n{}\n\t{}\n\tMatching Value(s):\n\t{}\n\n", query_path.display(), policy, output_query_string.trim_end(), matching.trim_end());
-                            }
-                        }
-                    } else if line_raw.len() == 2  && line_raw[0].to_lowercase().trim().starts_with("w") {
-                        let line_queries_raw = line_raw[1].split("|");
-
-                        for policy in gpos.iter() {
-                            let mut query_failed = false;
-                            for line_query in line_queries_raw.clone() {
-                                if !policy.query_gpo(&line_query.trim_start().trim_end()){
-                                    query_failed = true;
-                                    break
-                                }
-                            }
-                            if !query_failed {
-                                let mut matching = String::new();
-                                let full_query_strings: Vec<&str> = v.split("--").collect();
-                                let query_strings = full_query_strings[1].split("|");
-                                let mut output_query_string = "Query Condition(s):\n\t".to_string();
-                                for query in query_strings {
-                                    output_query_string += query.trim_start().trim_end();
-                                    output_query_string += "\n\t";
-                                }
-                                for line_query in line_queries_raw.clone() {
-                                    let matching_string = &policy.get_matching_conditions(&line_query.trim_start().trim_end());
-                                    if !matching_string.is_empty() && !matching.contains(matching_string) {
-                                        matching.push_str(matching_string);
-                                        matching.push_str("\n\t")
-                                    }
-                                }
-                                warnings += &format!("{}\n{}\n\t{}\n\tMatching Value(s):\n\t{}\n\n", query_path.display(), policy, output_query_string.trim_end(), matching.trim_end());
-                            }
-                        }
-                    } else if line_raw.len() == 2  && line_raw[0].to_lowercase().trim().starts_with("m") {
-                        let line_queries_raw = line_raw[1].split("|");
-                        let mut policy_exists = false;
-                        for policy in gpos.iter() {
-                            let mut query_failed = false;
-                            for line_query in line_queries_raw.clone() {
-                                if !policy.query_gpo(&line_query.trim_start().trim_end()){
-                                    query_failed = true;
-                                    break
-                                }
-                            }
-                            if !query_failed {
-                                policy_exists = true;
-                            }
-                        }
-                        if !policy_exists {
-                            let full_query_strings: Vec<&str> = v.split("--").collect();
-                            let query_strings = full_query_strings[1].split("|");
-                            let mut output_query_string = "Query Condition(s):\n\t".to_string();
-                            for query in query_strings {
-                                output_query_string += query.trim_start().trim_end();
-                                output_query_string += "\n\t";
-                            }
-                            missings += &format!("{}\n\t{}\n\n", query_path.display(), output_query_string.trim_end());
-                        }
-                    }
-                }
+            if certificate.is_efs_recovery_agent() {
+                reasons.push("EFS recovery agent");
+            }
+            if !reasons.is_empty() {
+                findings.push(CertificateFinding { reasons, gpo: policy, certificate });
             }
         }
     }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::applocker::ApplicationControl;
+    use crate::coverage::Coverage;
+    use crate::firewall::Firewall;
+    use crate::gpo::Details;
+    use crate::pki::PublicKeyPolicies;
+    use crate::preferences::Preferences;
 
-    if !undesirables.is_empty() {
-        u_file.write(format!("Undesirables\n{}", undesirables).as_bytes())?;
-        println!("Undesirables\n{}", undesirables);
+    fn gen_gpo(name: &str) -> GroupPolicy {
+        GroupPolicy {
+            name: name.to_string(),
+            details: Details::new(),
+            links: Vec::new(),
+            filtering: Vec::new(),
+            delegation: Vec::new(),
+            policies: Vec::new(),
+            application_control: ApplicationControl::new(),
+            firewall: Firewall::new(),
+            public_key_policies: PublicKeyPolicies::new(),
+            preferences: Preferences::new(),
+            wmi_filter: None,
+            coverage: Coverage::default(),
+        }
     }
-    if !desirables.is_empty() {
-        d_file.write(format!("Desirables\n{}", desirables).as_bytes())?;
-        println!("Desirables\n{}", desirables);
+
+    #[test]
+    fn test_analyze() {
+        let gpos = vec!(gen_gpo("Default Domain Policy"), gen_gpo("Servers"));
+        let queries: Vec<Query> = ["U -- Name::Default Domain Policy", "M -- Name::Workstations", "M -- Name::Servers", "// W -- Name::Servers"].iter().filter_map(|line| Query::parse(line).unwrap()).collect();
+        let analysis = analyze(&gpos, &queries, &NaiveDateTime::default());
+
+        assert_eq!(analysis.findings.len(), 2);
+        assert_eq!(analysis.findings_of(Severity::Undesirable).next().unwrap().gpo.unwrap().name, "Default Domain Policy");
+        assert_eq!(analysis.findings_of(Severity::Undesirable).next().unwrap().matching, vec!("Default Domain Policy"));
+        assert!(analysis.findings_of(Severity::Missing).next().unwrap().gpo.is_none());
+        assert_eq!(analysis.report(Severity::Missing), "Missings\n\n\tQuery Condition(s):\n\tName::Workstations\n\n");
+        assert_eq!(analysis.reports().len(), 2);
     }
-    if !warnings.is_empty() {
-        w_file.write(format!("Warning\n{}", warnings).as_bytes())?;
-        println!("Warning\n{}", warnings);
+
+    #[test]
+    fn test_analyze_expression() {
+        let gpos = vec!(gen_gpo("Default Domain Policy"), gen_gpo("Servers"));
+        let queries: Vec<Query> = ["W -- !Name::Servers | (Name::Workstations || Name::Default Domain Policy)", "D -- Name::Servers || Name::Default Domain Policy"].iter().filter_map(|line| Query::parse(line).unwrap()).collect();
+        let analysis = analyze(&gpos, &queries, &NaiveDateTime::default());
+
+        let warnings: Vec<&Finding> = analysis.findings_of(Severity::Warning).collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].matching, vec!("Default Domain Policy"));
+        let desirables: Vec<Vec<String>> = analysis.findings_of(Severity::Desirable).map(|finding| finding.matching.clone()).collect();
+        assert_eq!(desirables, vec!(vec!("Default Domain Policy".to_string()), vec!("Servers".to_string())));
     }
-    if !missings.is_empty() {
-        m_file.write(format!("Missings\n{}", missings).as_bytes())?;
-        println!("Missings\n{}", missings);
+
+    #[test]
+    fn test_analyze_at() {
+        let mut gpo = gen_gpo("PKI");
+        gpo.public_key_policies = PublicKeyPolicies::parse("Public Key Policies\nTrusted Root Certification Authorities\nIssued To │ Issued By │ Expiration Date\nContoso Root CA │ Contoso Root CA │ 1/1/2020\n");
+        let gpos = vec!(gpo);
+        let queries: Vec<Query> = Query::parse("U -- Certificate::Root::Status::Expired").unwrap().into_iter().collect();
+        let at = |year: i32| chrono::NaiveDate::from_ymd_opt(year, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        assert!(analyze(&gpos, &queries, &at(2019)).findings.is_empty());
+        assert_eq!(analyze(&gpos, &queries, &at(2024)).findings.len(), 1);
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_rule_findings() {
+        let gpos = vec!(gen_gpo("Default Domain Policy"));
+        // Only one of the two conditions of each rule matches.
+        let rules = "\
+[[rule]]
+id = \"GPO-001\"
+title = \"A default GPO is missing\"
+severity = \"missing\"
+remediation = \"Restore it with dcgpofix.\"
+conditions = [\"Name::Default Domain Policy\", \"Name::Default Domain Controllers Policy\"]
+
+[[rule]]
+id = \"GPO-002\"
+title = \"A default GPO is in use\"
+severity = \"warning\"
+conditions = [\"Name::Default Domain Policy\", \"Name::Default Domain Controllers Policy\"]
+";
+        let set = crate::rule::parse_str(Path::new("gpos.toml"), rules, &Default::default(), "");
+        let analysis = analyze(&gpos, &set.queries, &NaiveDateTime::default());
+
+        assert_eq!(analysis.findings.len(), 1);
+        assert_eq!(analysis.findings[0].query.rule.as_ref().unwrap().id, "GPO-002");
+        assert_eq!(analysis.report(Severity::Missing), "");
+        assert_eq!(analysis.report(Severity::Warning), "Warning\ngpos.toml\nRule: [GPO-002] A default GPO is in use\n\tName: Default Domain Policy | GPO Status: \n\tLinks: [  ]\n\tQuery Condition(s):\n\tName::Default Domain Policy || Name::Default Domain Controllers Policy\n\tMatching Value(s):\n\tDefault Domain Policy\n\n");
+    }
+
+    #[test]
+    fn test_certificate_findings() {
+        let mut gpo = gen_gpo("PKI");
+        gpo.public_key_policies = PublicKeyPolicies::parse("\
+Public Key Policies
+Trusted Root Certification Authorities
+Issued To │ Issued By │ Expiration Date │ Intended Purposes
+Microsoft Root Certificate Authority 2011 │ Microsoft Root Certificate Authority 2011 │ 3/22/2036 │ <All>
+Thumbprint │ 8F43288AD272F3103B6FB1428485EA3014C0BCFE
+Microsoft Root Authority Update │ Microsoft Root Authority Update │ 1/1/2030 │ <All>
+
+Encrypting File System
+Issued To │ Issued By │ Expiration Date │ Intended Purposes
+Administrator │ Administrator │ 1/1/2030 │ File Recovery
+");
+        let gpos = vec!(gpo);
+        let findings = certificate_findings(&gpos, &NaiveDateTime::default());
+
+        let reasons: Vec<(&str, Vec<&str>)> = findings.iter().map(|finding| (finding.certificate.subject.as_str(), finding.reasons.clone())).collect();
+        assert_eq!(reasons, vec!(("Microsoft Root Authority Update", vec!("Non-Microsoft root CA")), ("Administrator", vec!("EFS recovery agent"))));
+    }
+
+    #[test]
+    fn test_certificate_findings_without_thumbprint() {
+        // GPMC tables as html2text renders them, without a thumbprint column.
+        let mut gpo = gen_gpo("PKI");
+        gpo.public_key_policies = PublicKeyPolicies::parse("\
+Public Key Policies
+Trusted Root Certification Authorities
+Issued To                                 │Issued By                                 │Expiration Date│Intended Purposes
+──────────────────────────────────────────┼──────────────────────────────────────────┼───────────────┼─────────────────
+Microsoft Root Certificate Authority 2011 │Microsoft Root Certificate Authority 2011 │3/22/2036      │<All>
+──────────────────────────────────────────┼──────────────────────────────────────────┼───────────────┼─────────────────
+Contoso Root CA                           │Contoso Root CA                           │1/1/2030       │<All>
+");
+        let gpos = vec!(gpo);
+        let findings = certificate_findings(&gpos, &NaiveDateTime::default());
+
+        let reasons: Vec<(&str, Vec<&str>)> = findings.iter().map(|finding| (finding.certificate.subject.as_str(), finding.reasons.clone())).collect();
+        assert_eq!(reasons, vec!(("Microsoft Root Certificate Authority 2011", vec!("Unverified root CA (no thumbprint in report)")), ("Contoso Root CA", vec!("Non-Microsoft root CA"))));
+        assert!(findings[0].to_string().ends_with("\tPurposes: <All>"));
+    }
+}
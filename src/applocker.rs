@@ -0,0 +1,362 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Application control: AppLocker rule collections and Software Restriction Policies.
+//
+// AppLocker collections appear in the report as a heading ("Executable Rules", "DLL Rules", ...)
+// followed by an optional "Enforcement" row and a rule table headed by "Action │ User │ Name ...".
+// Rule conditions are read from a condition column when the table has one, or from the
+// "Path │", "Publisher │" and "File Hash │" detail rows printed beneath a rule.
+// Software Restriction Policies use "Path Rules", "Hash Rules", ... tables with a "Security Level" column.
+
+use core::fmt;
+use std::collections::HashSet;
+use serde::Serialize;
+use crate::gpo::matches_value;
+use crate::table;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum RuleCollection {
+    Executable,
+    WindowsInstaller,
+    Script,
+    PackagedApp,
+    Dll,
+}
+
+impl RuleCollection {
+    fn from_heading(line: &str) -> Option<Self> {
+        match line.trim().to_lowercase().as_str() {
+            "executable rules" => Some(Self::Executable),
+            "windows installer rules" => Some(Self::WindowsInstaller),
+            "script rules" => Some(Self::Script),
+            "packaged app rules" | "packaged app installer rules" => Some(Self::PackagedApp),
+            "dll rules" => Some(Self::Dll),
+            _ => None,
+        }
+    }
+    // Short names used in queries, e.g. AppLocker::Exe::Allow::::%OSDRIVE%\*
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::Executable => &["exe", "executable"],
+            Self::WindowsInstaller => &["msi", "windows installer"],
+            Self::Script => &["script"],
+            Self::PackagedApp => &["appx", "packaged app"],
+            Self::Dll => &["dll"],
+        }
+    }
+    fn is_collection(&self, value: &str) -> bool {
+        value.is_empty()
+            || self.aliases().iter().any(|alias| alias.eq_ignore_ascii_case(value))
+            || matches_value(&self.to_string(), value)
+    }
+}
+
+impl fmt::Display for RuleCollection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Executable => "Executable",
+            Self::WindowsInstaller => "Windows Installer",
+            Self::Script => "Script",
+            Self::PackagedApp => "Packaged app",
+            Self::Dll => "DLL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppLockerRule {
+    pub collection: RuleCollection,
+    pub action: String,
+    pub user: String,
+    pub name: String,
+    pub condition_type: String,
+    pub condition: String,
+    pub exceptions: String,
+}
+
+impl AppLockerRule {
+    fn new(collection: RuleCollection) -> Self {
+        Self {
+            collection,
+            action: String::new(),
+            user: String::new(),
+            name: String::new(),
+            condition_type: String::new(),
+            condition: String::new(),
+            exceptions: String::new(),
+        }
+    }
+    // Blank values match anything so a query can filter on a subset of the fields.
+    pub fn is_match(&self, collection: &str, action: &str, user: &str, condition: &str) -> bool {
+        self.collection.is_collection(collection)
+            && (action.is_empty() || matches_value(&self.action, action))
+            && (user.is_empty() || matches_value(&self.user, user))
+            && (condition.is_empty() || matches_value(&self.condition, condition))
+    }
+}
+
+impl fmt::Display for AppLockerRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AppLocker: {} | {} | User: {} | {}: {} | Name: {}", self.collection, self.action, self.user, self.condition_type, self.condition, self.name)
+    }
+}
+
+// SRP rule table headings. Other tables, e.g. the firewall "Inbound Rules", also end in " Rules".
+fn srp_rule_type(line: &str) -> Option<&'static str> {
+    match line.trim().to_lowercase().as_str() {
+        "certificate rules" => Some("Certificate"),
+        "hash rules" => Some("Hash"),
+        "internet zone rules" | "network zone rules" => Some("Network Zone"),
+        "path rules" => Some("Path"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SoftwareRestrictionRule {
+    pub rule_type: String,
+    pub value: String,
+    pub security_level: String,
+    pub description: String,
+}
+
+impl SoftwareRestrictionRule {
+    pub fn is_match(&self, value: &str, security_level: &str) -> bool {
+        (value.is_empty() || matches_value(&self.value, value))
+            && (security_level.is_empty() || matches_value(&self.security_level, security_level))
+    }
+}
+
+impl fmt::Display for SoftwareRestrictionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Software Restriction: {} | {} | Security Level: {} | Description: {}", self.rule_type, self.value, self.security_level, self.description)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplicationControl {
+    pub enforcement: Vec<(RuleCollection, String)>,
+    pub applocker_rules: Vec<AppLockerRule>,
+    pub srp_default_level: String,
+    pub srp_rules: Vec<SoftwareRestrictionRule>,
+}
+
+impl Default for ApplicationControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApplicationControl {
+    pub fn new() -> Self {
+        Self {
+            enforcement: Vec::new(),
+            applocker_rules: Vec::new(),
+            srp_default_level: String::new(),
+            srp_rules: Vec::new(),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        Self::parse_lines(content, &mut HashSet::new())
+    }
+
+    // Like parse, adding the line numbers of the rows it read to "parsed".
+    pub fn parse_lines(content: &str, parsed: &mut HashSet<usize>) -> Self {
+        let mut control = Self::new();
+        let mut collection: Option<RuleCollection> = None;
+        // Column indexes of the current AppLocker rule table.
+        let mut applocker_header: Option<Vec<String>> = None;
+        // Rule type ("Path", "Hash", ...) and header of the current SRP table.
+        let mut srp_type = String::new();
+        let mut srp_header: Option<Vec<String>> = None;
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                applocker_header = None;
+                srp_header = None;
+                continue;
+            }
+            if table::is_border(line) {
+                continue;
+            }
+            if !table::is_row(line) {
+                if let Some(c) = RuleCollection::from_heading(line) {
+                    collection = Some(c);
+                } else if let Some(rule_type) = srp_rule_type(line) {
+                    srp_type = rule_type.to_string();
+                }
+                continue;
+            }
+
+            let row = table::split_row(line);
+            let first = row[0].to_lowercase();
+
+            // Rule table headers
+            if table::column(&row, &["Action"]).is_some() && table::column(&row, &["User", "User or Group", "Users"]).is_some() {
+                applocker_header = Some(row);
+                parsed.insert(index);
+                continue;
+            }
+            if table::column(&row, &["Security Level"]).is_some() && first != "default security level" {
+                srp_header = Some(row);
+                parsed.insert(index);
+                continue;
+            }
+
+            if first == "default security level" {
+                control.srp_default_level = row.get(1).cloned().unwrap_or_default();
+                parsed.insert(index);
+            }
+            else if first == "enforcement" || first == "enforcement mode" {
+                if let Some(c) = collection {
+                    control.enforcement.push((c, row.get(1).cloned().unwrap_or_default()));
+                    parsed.insert(index);
+                }
+            }
+            else if let (Some(header), Some(c)) = (&applocker_header, collection) {
+                // Condition details printed beneath the last rule.
+                if row.len() == 2 && ["path", "publisher", "file hash", "hash"].contains(&first.as_str()) {
+                    if let Some(rule) = control.applocker_rules.last_mut() {
+                        rule.condition_type = row[0].clone();
+                        rule.condition = row[1].clone();
+                        parsed.insert(index);
+                    }
+                    continue;
+                }
+                let mut rule = AppLockerRule::new(c);
+                rule.action = table::cell(&row, table::column(header, &["Action"]));
+                rule.user = table::cell(&row, table::column(header, &["User", "User or Group", "Users"]));
+                rule.name = table::cell(&row, table::column(header, &["Name"]));
+                rule.condition_type = table::cell(&row, table::column(header, &["Rule Type", "Condition Type", "Type"]));
+                rule.condition = table::cell(&row, table::column(header, &["Condition", "Conditions", "Path", "Publisher", "File Hash"]));
+                rule.exceptions = table::cell(&row, table::column(header, &["Exceptions"]));
+                // Path rules are named after their path unless renamed.
+                if rule.condition.is_empty() && rule.condition_type.eq_ignore_ascii_case("path") {
+                    rule.condition = rule.name.clone();
+                }
+                control.applocker_rules.push(rule);
+                parsed.insert(index);
+            }
+            else if let Some(header) = &srp_header {
+                control.srp_rules.push(SoftwareRestrictionRule {
+                    rule_type: srp_type.clone(),
+                    value: row[0].clone(),
+                    security_level: table::cell(&row, table::column(header, &["Security Level"])),
+                    description: table::cell(&row, table::column(header, &["Description"])),
+                });
+                parsed.insert(index);
+            }
+        }
+
+        control
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // html2text 0.12 output of a report with AppLocker, SRP and firewall rule tables.
+    const REPORT: &str = "\
+Application Control Policies
+AppLocker
+Executable Rules
+
+───────────┬─────────────
+Enforcement│Enforce rules
+───────────┴─────────────
+
+─────────┬───────────────────────────────────────────────────────────────────────┬──────────────────────────────────────────────────────┬─────────┬──────────
+Action   │User                                                                   │Name                                                  │Rule Type│Exceptions
+─────────┼───────────────────────────────────────────────────────────────────────┼──────────────────────────────────────────────────────┼─────────┼──────────
+Allow    │Everyone                                                               │(Default Rule) All files located in the Windows folder│Path     │          
+─────────┼───────────────────────────────────────────────────────────────────────┴──────────────────────────────────────────────────────┴─────────┴──────────
+Path     │%WINDIR%\\*                                                             
+─────────┼───────────────────────────────────────────────────────────────────────┬──────────────────────────────────────────────────────┬─────────┬
+Allow    │Everyone                                                               │Allow the Temp folder                                 │Path     │          
+─────────┼───────────────────────────────────────────────────────────────────────┴──────────────────────────────────────────────────────┴─────────┴──────────
+Path     │C:\\Windows\\Temp\\*                                                      
+─────────┼───────────────────────────────────────────────────────────────────────┬──────────────────────────────────────────────────────┬─────────┬
+Deny     │LABS\\Contractors                                                       │Block PowerShell                                      │Publisher│          
+─────────┼───────────────────────────────────────────────────────────────────────┴──────────────────────────────────────────────────────┴─────────┴──────────
+Publisher│O=MICROSOFT CORPORATION, L=REDMOND, S=WASHINGTON, C=US\\*\\POWERSHELL.EXE
+─────────┴───────────────────────────────────────────────────────────────────────
+Software Restriction Policies
+
+──────────────────────┬──────────
+Default Security Level│Disallowed
+──────────────────────┴──────────
+Path Rules
+
+────────┬──────────────┬─────────────────
+Path    │Security Level│Description      
+────────┼──────────────┼─────────────────
+%TEMP%\\*│Unrestricted  │Installer scratch
+────────┴──────────────┴─────────────────
+Windows Defender Firewall with Advanced Security
+Inbound Rules
+
+─────────┬────────────
+Name     │Description 
+─────────┼────────────
+Allow SMB│File sharing
+─────────┴────────────
+";
+
+    #[test]
+    fn test_parse_applocker() {
+        let control = ApplicationControl::parse(REPORT);
+        assert_eq!(control.enforcement.len(), 1);
+        assert_eq!(control.applocker_rules.len(), 3);
+        assert_eq!(control.applocker_rules[0].condition, "%WINDIR%\\*");
+        assert_eq!(control.applocker_rules[1].condition, "C:\\Windows\\Temp\\*");
+        assert_eq!(control.applocker_rules[1].action, "Allow");
+        assert_eq!(control.applocker_rules[2].condition_type, "Publisher");
+        assert!(control.applocker_rules[0].is_match("exe", "Allow", "", "%WINDIR%\\*"));
+        assert!(!control.applocker_rules[0].is_match("dll", "", "", ""));
+        assert!(control.applocker_rules[2].is_match("", "Deny", ">Contractors", ">POWERSHELL.EXE"));
+    }
+
+    #[test]
+    fn test_parse_srp() {
+        let control = ApplicationControl::parse(REPORT);
+        assert_eq!(control.srp_default_level, "Disallowed");
+        assert_eq!(control.srp_rules.len(), 1);
+        assert_eq!(control.srp_rules[0].rule_type, "Path");
+        assert_eq!(srp_rule_type("Inbound Rules"), None);
+        assert_eq!(srp_rule_type("Hash Rules"), Some("Hash"));
+        assert!(control.srp_rules[0].is_match("<%TEMP%", "Unrestricted"));
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let path = std::env::temp_dir().join("gpo_insight_test_parse_applocker.txt");
+        std::fs::write(&path, format!("AppLocker Baseline\n{}", REPORT)).unwrap();
+        let test_gpo = crate::gpo::GroupPolicy::new(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let control = &test_gpo.application_control;
+        let conditions: Vec<&str> = control.applocker_rules.iter().map(|rule| rule.condition.as_str()).collect();
+        assert_eq!(conditions, vec!("%WINDIR%\\*", "C:\\Windows\\Temp\\*", "O=MICROSOFT CORPORATION, L=REDMOND, S=WASHINGTON, C=US\\*\\POWERSHELL.EXE"));
+        assert_eq!(control.applocker_rules[2].user, "LABS\\Contractors");
+        assert_eq!(control.srp_rules.len(), 1);
+        assert_eq!(control.srp_rules[0].value, "%TEMP%\\*");
+        assert_eq!(test_gpo.coverage.to_string(), "Coverage: 100.0% | Parsed: 13 | Unparsed: 0");
+    }
+}
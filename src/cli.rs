@@ -29,13 +29,13 @@ pub fn parse_input_path(path: &str) -> Result<PathBuf> {
                 let home = path.replace("~", v.to_str().with_context(|| "Could not convert the home directory into a string slice.")?);
                 let home_path = Path::new(&home);
                 if home_path.exists() {
-                    return Ok(home_path.to_path_buf());
+                    Ok(home_path.to_path_buf())
                 } else {
-                    return Err(anyhow!("The path {} could not be found.", home_path.display()))
+                    Err(anyhow!("The path {} could not be found.", home_path.display()))
                 }
             }
             None => {
-                return Err(anyhow!("The HOME environment variable could not be found."));
+                Err(anyhow!("The HOME environment variable could not be found."))
             }
         }
     }
@@ -43,10 +43,10 @@ pub fn parse_input_path(path: &str) -> Result<PathBuf> {
     else {
         let provided_path = Path::new(path);
         if provided_path.exists(){
-            return Ok(Path::new(path).to_path_buf())
+            Ok(Path::new(path).to_path_buf())
         }
         else {
-            return Err(anyhow!("The path {} could not be found.", provided_path.display()))
+            Err(anyhow!("The path {} could not be found.", provided_path.display()))
         }
     }
 }
@@ -60,13 +60,13 @@ pub fn parse_output_path(path: &str) -> Result<PathBuf> {
                 let home_path = Path::new(&home);
                 let home_path = home_path.join("gpo-insight");
                 if home_path.exists() {
-                    return Err(anyhow!("The output directory {} already exists.", home_path.display()))
+                    Err(anyhow!("The output directory {} already exists.", home_path.display()))
                 } else {
-                    return Ok(home_path.to_path_buf());
+                    Ok(home_path.to_path_buf())
                 }
             }
             None => {
-                return Err(anyhow!("The HOME environment variable could not be found."));
+                Err(anyhow!("The HOME environment variable could not be found."))
             }
         }
     }
@@ -77,16 +77,16 @@ pub fn parse_output_path(path: &str) -> Result<PathBuf> {
         let new_path = Path::new(&current_dir_string);
         let new_path = new_path.join("gpo-insight");
         if new_path.exists() {
-            return Err(anyhow!("The output directory {} already exists.", new_path.display()))
+            Err(anyhow!("The output directory {} already exists.", new_path.display()))
         } else {
-            return Ok(new_path.to_path_buf())
+            Ok(new_path.to_path_buf())
         }
     }
     // Check if the directory exists
     else {
         let curr_path = Path::new(path);
         if curr_path.exists() {
-            return Err(anyhow!("The output directory {} already exists.", curr_path.display()))
+            Err(anyhow!("The output directory {} already exists.", curr_path.display()))
         } else {
             Ok(curr_path.to_path_buf())
         }
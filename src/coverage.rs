@@ -0,0 +1,189 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Which report rows the parser understood.
+//
+// Every table row of a GPO report is either parsed into the GroupPolicy model, skipped because
+// it only labels a table, or unparsed. Unparsed rows are blind spots: a "missing" finding for a
+// setting in one of them says more about the parser than about the GPO.
+
+use core::fmt;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use anyhow::Result;
+use serde::Serialize;
+use crate::gpo::GroupPolicy;
+use crate::table;
+
+// Column titles of the report tables. A row made only of these labels a table.
+const HEADER_CELLS: &[&str] = &[
+    "name",
+    "display name",
+    "setting",
+    "settings",
+    "policy",
+    "comment",
+    "location",
+    "enforced",
+    "link status",
+    "path",
+    "allowed permissions",
+    "inherited",
+    "winning gpo",
+    "value",
+    "state",
+    "action",
+    "issued to",
+    "issued by",
+    "expiration date",
+    "intended purposes",
+    "namespace",
+    "query",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SectionCoverage {
+    pub heading: String,
+    pub parsed: usize,
+    pub skipped: usize,
+    pub unparsed: Vec<String>,
+}
+
+impl SectionCoverage {
+    fn new(heading: &str) -> Self {
+        Self {
+            heading: heading.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Coverage {
+    pub sections: Vec<SectionCoverage>,
+}
+
+impl Coverage {
+    // "parsed" holds the line numbers read by GroupPolicy::new and the section parsers.
+    pub fn new(content: &str, parsed: &HashSet<usize>) -> Self {
+        let mut coverage = Self::default();
+        let mut section = SectionCoverage::new("");
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || table::is_border(line) {
+                continue;
+            }
+            if !table::is_row(line) {
+                if section.parsed > 0 || section.skipped > 0 || !section.unparsed.is_empty() {
+                    coverage.sections.push(section);
+                }
+                section = SectionCoverage::new(line.trim());
+                continue;
+            }
+            let row = table::split_row(line);
+            if parsed.contains(&index) {
+                section.parsed += 1;
+            } else if row.iter().all(|cell| cell.is_empty() || HEADER_CELLS.contains(&cell.to_lowercase().as_str())) {
+                section.skipped += 1;
+            } else {
+                section.unparsed.push(row.join(" │ "));
+            }
+        }
+        if section.parsed > 0 || section.skipped > 0 || !section.unparsed.is_empty() {
+            coverage.sections.push(section);
+        }
+        coverage
+    }
+
+    pub fn parsed(&self) -> usize {
+        self.sections.iter().map(|section| section.parsed).sum()
+    }
+
+    pub fn unparsed(&self) -> usize {
+        self.sections.iter().map(|section| section.unparsed.len()).sum()
+    }
+
+    // Share of the rows holding data that were parsed. Skipped rows hold no data.
+    pub fn percentage(&self) -> f64 {
+        percentage(self.parsed(), self.unparsed())
+    }
+}
+
+fn percentage(parsed: usize, unparsed: usize) -> f64 {
+    if parsed + unparsed == 0 {
+        return 100.0;
+    }
+    parsed as f64 * 100.0 / (parsed + unparsed) as f64
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Coverage: {:.1}% | Parsed: {} | Unparsed: {}", self.percentage(), self.parsed(), self.unparsed())?;
+        for section in self.sections.iter().filter(|section| !section.unparsed.is_empty()) {
+            write!(f, "\n\tSection: {} | Parsed: {} | Skipped: {} | Unparsed: {}", section.heading, section.parsed, section.skipped, section.unparsed.len())?;
+            for row in section.unparsed.iter() {
+                write!(f, "\n\t\t{}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Write "coverage.txt" with the coverage of every GPO. Returns the overall percentage.
+pub fn write_report(gpos: &[GroupPolicy], output_path: &Path) -> Result<f64> {
+    let parsed: usize = gpos.iter().map(|gpo| gpo.coverage.parsed()).sum();
+    let unparsed: usize = gpos.iter().map(|gpo| gpo.coverage.unparsed()).sum();
+    let overall = percentage(parsed, unparsed);
+
+    let mut report = format!("Overall Coverage: {:.1}% | Parsed: {} | Unparsed: {}\n\n", overall, parsed, unparsed);
+    for gpo in gpos.iter() {
+        report += &format!("Name: {}\n\t{}\n", gpo.name, gpo.coverage);
+        if gpo.is_empty() {
+            report += "\tNo settings were parsed from this GPO.\n";
+        }
+        report += "\n";
+    }
+    let mut file = File::create(output_path.join("coverage.txt"))?;
+    file.write_all(report.as_bytes())?;
+
+    Ok(overall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let content = "\
+Security Settings
+Policy │ Setting
+Minimum password length │ 14 characters
+Mystery setting │ 1
+Firewall
+Name │ Action
+Block SMB │ Block
+";
+        let parsed: HashSet<usize> = HashSet::from([2, 6]);
+        let coverage = Coverage::new(content, &parsed);
+        assert_eq!(coverage.sections.len(), 2);
+        assert_eq!(coverage.sections[0], SectionCoverage { heading: "Security Settings".to_string(), parsed: 1, skipped: 1, unparsed: vec!("Mystery setting │ 1".to_string()) });
+        assert_eq!(coverage.sections[1].parsed, 1);
+        assert_eq!(coverage.percentage(), 200.0 / 3.0);
+        assert_eq!(coverage.to_string(), "Coverage: 66.7% | Parsed: 2 | Unparsed: 1\n\tSection: Security Settings | Parsed: 1 | Skipped: 1 | Unparsed: 1\n\t\tMystery setting │ 1");
+    }
+}
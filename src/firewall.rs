@@ -0,0 +1,339 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Windows Defender Firewall with Advanced Security.
+//
+// Profiles are listed under "Domain Profile Settings", "Private Profile Settings" and
+// "Public Profile Settings" as "Setting │ Value" rows. Rules are listed under "Inbound Rules"
+// and "Outbound Rules", either as one row per rule with a column per property, or as a
+// "Name │ Description" row followed by "Property │ Value" rows for that rule.
+
+use core::fmt;
+use std::collections::HashSet;
+use serde::Serialize;
+use crate::gpo::matches_value;
+use crate::table;
+
+// Rule properties printed beneath a rule's name when the report uses the expanded layout.
+const RULE_PROPERTIES: &[&str] = &[
+    "enabled",
+    "program",
+    "service",
+    "action",
+    "protocol",
+    "local port",
+    "remote port",
+    "local address",
+    "remote address",
+    "profile",
+    "direction",
+    "security",
+    "edge traversal",
+    "interface types",
+    "icmp settings",
+    "local principals",
+    "remote users",
+    "remote computers",
+    "group",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FirewallProfile {
+    pub name: String,
+    pub settings: Vec<(String, String)>,
+}
+
+impl FirewallProfile {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            settings: Vec::new(),
+        }
+    }
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.settings.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+    // "On (recommended)" and "Off" are the values printed for the firewall state.
+    pub fn is_enabled(&self) -> Option<bool> {
+        self.setting("Firewall state").map(|state| state.to_lowercase().starts_with("on"))
+    }
+    pub fn is_match(&self, profile: &str, setting: &str, value: &str) -> bool {
+        if !profile.is_empty() && !matches_value(&self.name, profile) {
+            return false;
+        }
+        self.settings.iter().any(|(key, current)| {
+            (setting.is_empty() || matches_value(key, setting))
+                && (value.is_empty() || match value.strip_prefix('!') {
+                    Some(stripped) => !matches_value(current, stripped),
+                    None => matches_value(current, value),
+                })
+        })
+    }
+}
+
+impl fmt::Display for FirewallProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.is_enabled() {
+            Some(true) => "On",
+            Some(false) => "Off",
+            None => "Not Configured",
+        };
+        write!(f, "Firewall Profile: {} | State: {} | Inbound: {} | Outbound: {}", self.name, state, self.setting("Inbound connections").unwrap_or(""), self.setting("Outbound connections").unwrap_or(""))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FirewallRule {
+    pub direction: String,
+    pub name: String,
+    pub description: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl FirewallRule {
+    fn new(direction: &str, name: &str) -> Self {
+        Self {
+            direction: direction.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            properties: Vec::new(),
+        }
+    }
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+    // Rules without an "Enabled" property are enabled.
+    pub fn is_enabled(&self) -> bool {
+        !self.property("Enabled").map(|enabled| enabled.eq_ignore_ascii_case("false") || enabled.eq_ignore_ascii_case("no")).unwrap_or(false)
+    }
+    fn field(&self, name: &str) -> Option<&str> {
+        if name.eq_ignore_ascii_case("name") {
+            Some(&self.name)
+        } else if name.eq_ignore_ascii_case("description") {
+            Some(&self.description)
+        } else {
+            self.property(name)
+        }
+    }
+    // Disabled rules never match. Blank values match anything.
+    pub fn is_match(&self, direction: &str, action: &str, field: &str, value: &str) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        if !direction.is_empty() && !matches_value(&self.direction, direction) {
+            return false;
+        }
+        if !action.is_empty() && !matches_value(self.property("Action").unwrap_or(""), action) {
+            return false;
+        }
+        if field.is_empty() {
+            return value.is_empty();
+        }
+        match self.field(field) {
+            Some(current) => value.is_empty() || matches_value(current, value),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for FirewallRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut output_string = format!("Firewall Rule: {} | {}", self.direction, self.name);
+        for (key, value) in self.properties.iter() {
+            output_string += &format!(" | {}: {}", key, value);
+        }
+        write!(f, "{}", output_string)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Firewall {
+    pub profiles: Vec<FirewallProfile>,
+    pub rules: Vec<FirewallRule>,
+}
+
+impl Default for Firewall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Firewall {
+    pub fn new() -> Self {
+        Self {
+            profiles: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        Self::parse_lines(content, &mut HashSet::new())
+    }
+
+    // Like parse, adding the line numbers of the rows it read to "parsed".
+    pub fn parse_lines(content: &str, parsed: &mut HashSet<usize>) -> Self {
+        let mut firewall = Self::new();
+        let mut firewall_block = false;
+        // Index of the profile being read, or the direction of the rules being read.
+        let mut profile: Option<usize> = None;
+        let mut direction = String::new();
+        let mut header: Vec<String> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || table::is_border(line) {
+                continue;
+            }
+            if table::is_section_heading(line) {
+                let heading = line.trim().to_lowercase();
+                firewall_block = heading.ends_with("firewall with advanced security");
+                profile = None;
+                direction = String::new();
+                continue;
+            }
+            if !firewall_block {
+                continue;
+            }
+            if !table::is_row(line) {
+                let heading = line.trim();
+                let lowered = heading.to_lowercase();
+                if let Some(name) = lowered.strip_suffix(" profile settings").or(lowered.strip_suffix(" profile")) {
+                    let name = heading[..name.len()].to_string();
+                    firewall.profiles.push(FirewallProfile::new(&name));
+                    profile = Some(firewall.profiles.len() - 1);
+                    direction = String::new();
+                } else if lowered == "inbound rules" || lowered == "outbound rules" {
+                    direction = heading.split(' ').next().unwrap_or_default().to_string();
+                    profile = None;
+                    header = Vec::new();
+                } else if lowered.ends_with(" rules") || lowered == "global settings" {
+                    profile = None;
+                    direction = String::new();
+                }
+                continue;
+            }
+
+            let row = table::split_row(line);
+            if let Some(profile_index) = profile {
+                if row.len() >= 2 && !(row[0].eq_ignore_ascii_case("setting") || row[0].eq_ignore_ascii_case("policy")) {
+                    firewall.profiles[profile_index].settings.push((row[0].clone(), row[1].clone()));
+                    parsed.insert(index);
+                }
+            }
+            else if !direction.is_empty() {
+                if table::column(&row, &["Name"]) == Some(0) && (row.len() == 2 || table::column(&row, &["Action"]).is_some()) {
+                    header = row;
+                    parsed.insert(index);
+                    continue;
+                }
+                if header.len() > 2 {
+                    // One rule per row
+                    let mut rule = FirewallRule::new(&direction, &row[0]);
+                    for (index, column) in header.iter().enumerate().skip(1) {
+                        let value = table::cell(&row, Some(index));
+                        if column.eq_ignore_ascii_case("description") {
+                            rule.description = value;
+                        } else if !value.is_empty() {
+                            rule.properties.push((column.clone(), value));
+                        }
+                    }
+                    firewall.rules.push(rule);
+                    parsed.insert(index);
+                }
+                else if RULE_PROPERTIES.contains(&row[0].to_lowercase().as_str()) && !firewall.rules.is_empty() {
+                    let rule = firewall.rules.last_mut().unwrap();
+                    rule.properties.push((row[0].clone(), table::cell(&row, Some(1))));
+                    parsed.insert(index);
+                }
+                else {
+                    let mut rule = FirewallRule::new(&direction, &row[0]);
+                    rule.description = table::cell(&row, Some(1));
+                    firewall.rules.push(rule);
+                    parsed.insert(index);
+                }
+            }
+        }
+
+        firewall
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "\
+Windows Defender Firewall with Advanced Security
+Global Settings
+Policy │ Setting
+Policy version │ 2.10
+
+Domain Profile Settings
+Policy │ Setting
+Firewall state │ On
+Inbound connections │ Block
+Outbound connections │ Allow
+
+Public Profile Settings
+Policy │ Setting
+Firewall state │ Off
+
+Inbound Rules
+────────────────────
+Name │ Description
+────────────────────
+Remote Desktop │ Allow RDP
+Enabled │ True
+Program │ System
+Action │ Allow
+Local port │ 3389
+Remote address │ Any
+Stale rule │
+Enabled │ False
+Action │ Allow
+Remote address │ Any
+
+Outbound Rules
+Name │ Action │ Remote Port
+Block SMB │ Block │ 445
+
+Administrative Templates
+Policy │ Setting │ Comment
+Turn off multicast name resolution │ Enabled │
+";
+
+    #[test]
+    fn test_parse_profiles() {
+        let firewall = Firewall::parse(REPORT);
+        assert_eq!(firewall.profiles.len(), 2);
+        assert_eq!(firewall.profiles[0].name, "Domain");
+        assert_eq!(firewall.profiles[0].is_enabled(), Some(true));
+        assert_eq!(firewall.profiles[1].is_enabled(), Some(false));
+        assert!(firewall.profiles[0].is_match("Domain", "Inbound connections", "Block"));
+        assert!(firewall.profiles[1].is_match("", "Firewall state", "!<On"));
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let firewall = Firewall::parse(REPORT);
+        assert_eq!(firewall.rules.len(), 3);
+        assert_eq!(firewall.rules[0].property("Local port"), Some("3389"));
+        assert!(firewall.rules[0].is_match("Inbound", "Allow", "Remote address", "Any"));
+        // Disabled rules are ignored
+        assert!(!firewall.rules[1].is_match("Inbound", "Allow", "Remote address", "Any"));
+        assert_eq!(firewall.rules[2].direction, "Outbound");
+        assert!(firewall.rules[2].is_match("Outbound", "Block", "Remote Port", "445"));
+    }
+}
@@ -16,22 +16,204 @@
 
 use core::fmt;
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, File}, io::{BufRead, BufReader, Read, Write}, path::{
         Path, 
         PathBuf
-    }
+    },
+    sync::Mutex
 };
 use anyhow::{Result, anyhow};
 use console::Style;
 use encoding_rs_io::DecodeReaderBytesBuilder;
-use html2text;
 use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use crate::applocker::ApplicationControl;
+use crate::firewall::Firewall;
+use crate::pki::PublicKeyPolicies;
+use chrono::NaiveDateTime;
+use crate::preferences::Preferences;
+use crate::wmi::WmiFilter;
+use crate::table;
+use crate::setting::{matches_date, ListQuery, SettingValue};
+use crate::principal::Principal;
+use crate::coverage::Coverage;
+use crate::query::{Condition, Field};
+
+// Rows of the Details table read into Details.
+const DETAILS_ROWS: &[&str] = &["Domain", "Owner", "Created", "Modified", "Unique ID", "GPO Status", "Computer Revision", "Computer Version", "User Revision", "User Version"];
 
 lazy_static!{
     static ref ERR_STYLE: Style = Style::new().red().bold();
+    // Compiled "~/pattern/flags" values, so a pattern is only compiled once per run.
+    static ref PATTERNS: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+}
+
+// The byte offset of the "/" closing a pattern that starts with "~/", if it is closed.
+// A "/" inside the pattern is escaped as "\/".
+pub fn pattern_end(value: &str) -> Option<usize> {
+    if !value.starts_with("~/") {
+        return None;
+    }
+    let mut escaped = false;
+    for (index, c) in value.char_indices().skip("~/".len()) {
+        match c {
+            '/' if !escaped => return Some(index),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+// Compile a "~/pattern/flags" value. Flags are "i" (ignore case), "m" (multi-line), "s" ("." matches
+// new lines) and "x" (ignore whitespace).
+pub fn parse_pattern(value: &str) -> Result<Regex> {
+    let Some(end) = pattern_end(value) else {
+        return Err(anyhow!("The pattern is never closed with \"/\", i.e. \"~/^S-1-5-21-.*-5\\d\\d$/\"."));
+    };
+    let mut builder = RegexBuilder::new(&value["~/".len()..end]);
+    for flag in value[end + "/".len()..].chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            _ => return Err(anyhow!("Unknown pattern flag \"{}\". Expected i, m, s or x.", flag)),
+        };
+    }
+    // The regex error spans several lines with a marker under the offending text; keep the reason.
+    builder.build().map_err(|error| {
+        let message = error.to_string();
+        let reason = message.lines().rev().find_map(|line| line.strip_prefix("error: ")).unwrap_or(&message).to_string();
+        anyhow!("Invalid pattern: {}.", reason)
+    })
+}
+
+// A "glob[...]" value as an anchored regular expression. "*" is any text and "?" any one character.
+fn parse_glob(glob: &str, case_sensitive: bool) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Ok(RegexBuilder::new(&pattern).case_insensitive(!case_sensitive).dot_matches_new_line(true).build()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    Equals,
+    StartsWith,
+    EndsWith,
+    Contains,
+    Glob,
+    Pattern,
+}
+
+// A query value split into its modifier and the text it compares. A leading "=" makes any of the
+// comparisons case sensitive; patterns use their own "i" flag instead. A "\\" before a modifier
+// makes it part of the text, i.e. "\\*foo" equals "*foo".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matcher<'a> {
+    pub mode: MatchMode,
+    pub case_sensitive: bool,
+    pub text: &'a str,
+    value: &'a str,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn parse(value: &'a str) -> Self {
+        let (case_sensitive, rest) = match value.strip_prefix('=') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        if let Some(literal) = rest.strip_prefix('\\').filter(|literal| starts_with_modifier(literal)) {
+            return Self { mode: MatchMode::Equals, case_sensitive, text: literal, value };
+        }
+        // "*S-1-5-32-544" is how Windows writes a SID, not "contains".
+        let is_sid = rest.get(.."*S-1-".len()).map(|prefix| prefix.eq_ignore_ascii_case("*S-1-")).unwrap_or(false);
+        let (mode, text) = if rest.starts_with("~/") {
+            (MatchMode::Pattern, rest)
+        } else if let Some(glob) = rest.strip_prefix("glob[").and_then(|glob| glob.strip_suffix(']')) {
+            (MatchMode::Glob, glob)
+        } else if let Some(stripped) = rest.strip_prefix('*').filter(|_| !is_sid) {
+            (MatchMode::Contains, stripped)
+        } else if let Some(stripped) = rest.strip_prefix('>') {
+            (MatchMode::EndsWith, stripped)
+        } else if let Some(stripped) = rest.strip_prefix('<') {
+            (MatchMode::StartsWith, stripped)
+        } else {
+            (MatchMode::Equals, rest)
+        };
+        Self { mode, case_sensitive, text, value }
+    }
+
+    // Whether the value uses a modifier or an escape rather than being a plain value.
+    pub fn has_modifier(&self) -> bool {
+        self.mode != MatchMode::Equals || self.text.len() != self.value.len()
+    }
+
+    // Delegation permissions have always matched a plain value anywhere in the permission.
+    pub fn or_contains(mut self) -> Self {
+        if self.mode == MatchMode::Equals {
+            self.mode = MatchMode::Contains;
+        }
+        self
+    }
+
+    // Globs and patterns are compiled once per run.
+    fn regex(&self) -> Option<Regex> {
+        let mut patterns = PATTERNS.lock().unwrap();
+        if !patterns.contains_key(self.value) {
+            let regex = match self.mode {
+                MatchMode::Glob => parse_glob(self.text, self.case_sensitive),
+                _ => parse_pattern(self.text),
+            };
+            patterns.insert(self.value.to_string(), regex.ok()?);
+        }
+        patterns.get(self.value).cloned()
+    }
+
+    pub fn is_match(&self, field: &str) -> bool {
+        if matches!(self.mode, MatchMode::Glob | MatchMode::Pattern) {
+            return self.regex().map(|regex| regex.is_match(field)).unwrap_or(false);
+        }
+        let (field, text) = if self.case_sensitive {
+            (field.to_string(), self.text.to_string())
+        } else {
+            (field.to_lowercase(), self.text.to_lowercase())
+        };
+        match self.mode {
+            MatchMode::StartsWith => field.starts_with(&text),
+            MatchMode::EndsWith => field.ends_with(&text),
+            MatchMode::Contains => field.contains(&text),
+            _ => field == text,
+        }
+    }
+}
+
+fn starts_with_modifier(value: &str) -> bool {
+    value.starts_with(['=', '*', '>', '<']) || value.starts_with("~/") || value.starts_with("glob[")
 }
 
-pub fn breakdown_gpo(input_path: &PathBuf, output_path: &PathBuf) -> Result<()> {
+// Whether a value uses one of the matching modifiers rather than a plain value.
+pub fn has_modifier(value: &str) -> bool {
+    Matcher::parse(value).has_modifier()
+}
+
+// Shared value matching for query fields. ">" is "Ends With", "<" is "Starts With", "*" is "Contains",
+// "glob[...]" is a wildcard match, "~/pattern/flags" is a regular expression search, otherwise equals.
+pub fn matches_value(field: &str, value: &str) -> bool {
+    Matcher::parse(value).is_match(field)
+}
+
+// Split a GPO export into one HTML file per GPO. Returns the number of GPOs found.
+pub fn breakdown_gpo(input_path: &PathBuf, output_path: &PathBuf) -> Result<usize> {
     let mut output = Path::new(&output_path);
     if output.exists(){
         return Err(anyhow!("The output directory {} already exists!", output.display()))
@@ -86,29 +268,21 @@ pub fn breakdown_gpo(input_path: &PathBuf, output_path: &PathBuf) -> Result<()>
                             loop {
                                 out_attempt += 1;
                                 let mut title = title_buf.clone();
-                                title.push_str("(");
+                                title.push('(');
                                 title.push_str(&out_attempt.to_string());
-                                title.push_str(")");
+                                title.push(')');
                                 title.push_str(".html");
                                 let out_file_attempt = output.join(&title);
                                 let out_file = Path::new(&out_file_attempt);
                                 if !out_file.exists(){
-
-                                    #[cfg(debug_assertions)]
-                                    println!("{}", out_file.display());
-
                                     let mut file = File::create(out_file)?;
-                                    file.write(content_buf.as_bytes())?;
+                                    file.write_all(content_buf.as_bytes())?;
                                     break
                                 }
                             }
                         } else{
-
-                            #[cfg(debug_assertions)]
-                            println!("{}", out_file.display());
-
                             let mut file = File::create(out_file)?;
-                            file.write(content_buf.as_bytes())?;
+                            file.write_all(content_buf.as_bytes())?;
                         }
                     }
                 }
@@ -119,20 +293,19 @@ pub fn breakdown_gpo(input_path: &PathBuf, output_path: &PathBuf) -> Result<()>
                 }
             }
             Err(e) => {
-                println!("Error reading line {}: {}", i, e);
+                return Err(anyhow!("Error reading line {}: {}", i, e));
             }
         }
     }
 
-    println!("GPO Breakdown successful. {} GPOs detected.", count_buf);
-
-    Ok(())
+    Ok(count_buf)
 }
 
-pub fn gpo_to_text(output_path: &PathBuf) -> Result<()> {
+// Convert every HTML file from breakdown_gpo to text. Returns the number of files converted.
+pub fn gpo_to_text(output_path: &Path) -> Result<usize> {
     let html_path_buf = output_path.join("html");
     let html_path = Path::new(&html_path_buf);
-    let files = fs::read_dir(&html_path)?;
+    let files = fs::read_dir(html_path)?;
     let mut files_count = 0;
 
     let txt_path_buf = output_path.join("txt");
@@ -176,22 +349,25 @@ pub fn gpo_to_text(output_path: &PathBuf) -> Result<()> {
             let output_line = line.to_string() + "\n";
             if !valid && line.contains("────"){
                 valid = true;
-                output_file.write(output_line.as_bytes())?;
+                output_file.write_all(output_line.as_bytes())?;
             }
             if valid {
-                output_file.write(output_line.as_bytes())?;
+                output_file.write_all(output_line.as_bytes())?;
             }
         }
         files_count += 1;
     }
 
-    println!("{} GPOs successfully converted from HTML to TXT.", files_count);
-
-    Ok(())
+    Ok(files_count)
 }
 
-pub fn text_to_struct(output_path: &PathBuf) -> Result<Vec<GroupPolicy>> {
+// A text file that could not be parsed into a GroupPolicy and the reason why.
+pub type ParseFailure = (PathBuf, anyhow::Error);
+
+// Parse every text file from gpo_to_text. Files that could not be parsed are returned with their error.
+pub fn text_to_struct(output_path: &Path) -> Result<(Vec<GroupPolicy>, Vec<ParseFailure>)> {
     let mut policies: Vec<GroupPolicy> = Vec::new();
+    let mut failures: Vec<ParseFailure> = Vec::new();
     let text_dir_path = output_path.join("txt");
     let files = fs::read_dir(text_dir_path)?;
 
@@ -212,41 +388,153 @@ pub fn text_to_struct(output_path: &PathBuf) -> Result<Vec<GroupPolicy>> {
                     policies.push(g);
                 }
                 Err(e) => {
-                    println!("Could not parse GPO {}.\n{}", path.display(), e);
+                    failures.push((path, e));
                 }
             }
         }
     }
 
-    Ok(policies)
+    Ok((policies, failures))
 }
 
-#[derive(Debug, Clone)]
+pub fn struct_to_json(gpos: &[GroupPolicy], output_path: &Path) -> Result<()> {
+    let json_path_buf = output_path.join("json");
+    let json_path = Path::new(&json_path_buf);
+    if !json_path.exists() {
+        fs::create_dir_all(json_path)?;
+    }
+
+    let mut file = File::create(output_path.join("gpos.json"))?;
+    file.write_all(serde_json::to_string_pretty(gpos)?.as_bytes())?;
+
+    // Names used in this run, so GPOs with the same name get their own file while files from
+    // an earlier run are overwritten. A GPO without a name is named after its GUID, or "unnamed".
+    let mut used: HashSet<String> = HashSet::new();
+    for gpo in gpos.iter() {
+        let name = [gpo.name.trim(), gpo.details.id.trim(), "unnamed"].into_iter().find(|name| !name.is_empty()).unwrap_or_default();
+        let title = name.replace("/", "_").replace("\\", "_");
+        let mut out_name = format!("{}.json", title);
+        let mut out_attempt = 0;
+        while !used.insert(out_name.to_lowercase()) {
+            out_attempt += 1;
+            out_name = format!("{}({}).json", title, out_attempt);
+        }
+        let mut file = File::create(json_path.join(out_name))?;
+        file.write_all(serde_json::to_string_pretty(gpo)?.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum GpoStatus {
+    AllSettingsEnabled,
+    UserSettingsDisabled,
+    ComputerSettingsDisabled,
+    AllSettingsDisabled,
+}
+
+impl GpoStatus {
+    // "Enabled", "User configuration settings disabled", "Computer configuration settings disabled" or "All settings disabled".
+    fn from_value(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if value == "enabled" || value == "all settings enabled" {
+            Some(Self::AllSettingsEnabled)
+        } else if value.starts_with("user") && value.ends_with("disabled") {
+            Some(Self::UserSettingsDisabled)
+        } else if value.starts_with("computer") && value.ends_with("disabled") {
+            Some(Self::ComputerSettingsDisabled)
+        } else if value.starts_with("all") && value.ends_with("disabled") {
+            Some(Self::AllSettingsDisabled)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for GpoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Self::AllSettingsEnabled => "Enabled",
+            Self::UserSettingsDisabled => "User configuration settings disabled",
+            Self::ComputerSettingsDisabled => "Computer configuration settings disabled",
+            Self::AllSettingsDisabled => "All settings disabled",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+// The version of one half of the GPO as stored in Active Directory and in SYSVOL.
+// The two only differ while replication is incomplete or when SYSVOL was edited directly.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Revision {
+    pub ad: u32,
+    pub sysvol: u32,
+}
+
+impl Revision {
+    // "3 (AD), 3 (SYSVOL)" or "AD (3), SYSVOL (3)"
+    fn from_value(value: &str) -> Option<Self> {
+        let mut ad: Option<u32> = None;
+        let mut sysvol: Option<u32> = None;
+        for part in value.split(',') {
+            let lowered = part.to_lowercase();
+            let number = part.chars().filter(|c| c.is_ascii_digit()).collect::<String>().parse::<u32>().ok();
+            if lowered.contains("sysvol") {
+                sysvol = number;
+            } else if lowered.contains("ad") {
+                ad = number;
+            }
+        }
+        Some(Self { ad: ad?, sysvol: sysvol? })
+    }
+    fn is_consistent(&self) -> bool {
+        self.ad == self.sysvol
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AD {} / SYSVOL {}", self.ad, self.sysvol)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Details {
     pub id: String,
-    pub status: String,
+    pub status: Option<GpoStatus>,
     pub domain: String,
     pub owner: String,
-    pub created: String,
-    pub modified: String,
+    pub created: Option<NaiveDateTime>,
+    pub modified: Option<NaiveDateTime>,
+    pub computer_revision: Option<Revision>,
+    pub user_revision: Option<Revision>,
+}
+
+impl Default for Details {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Details {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             id: String::new(),
-            status: String::new(),
+            status: None,
             domain: String::new(),
             owner: String::new(),
-            created: String::new(),
-            modified: String::new(),
+            created: None,
+            modified: None,
+            computer_revision: None,
+            user_revision: None,
         }
     }
     fn set_id(&mut self, value: String) {
         self.id = value;
     }
     fn set_status(&mut self, value: String) {
-        self.status = value;
+        self.status = GpoStatus::from_value(&value);
     }
     fn set_domain(&mut self, value: String) {
         self.domain = value;
@@ -255,100 +543,180 @@ impl Details {
         self.owner = value;
     }
     fn set_created(&mut self, value: String) {
-        self.created = value;
+        self.created = table::parse_date(&value);
     }
     fn set_modified(&mut self, value: String) {
-        self.modified = value;
+        self.modified = table::parse_date(&value);
+    }
+    fn set_computer_revision(&mut self, value: String) {
+        self.computer_revision = Revision::from_value(&value);
+    }
+    fn set_user_revision(&mut self, value: String) {
+        self.user_revision = Revision::from_value(&value);
     }
     fn is_empty(&mut self) -> bool {
-        return self.id.is_empty() && self.status.is_empty() && self.domain.is_empty() && self.owner.is_empty() && self.created.is_empty() && self.modified.is_empty()
-    }
-    // fn is_id(self, id: &str) -> bool {
-    //     self.id.to_lowercase() == id.to_lowercase()
-    // }
-    // fn is_enabled(self) -> bool {
-    //     self.status.to_lowercase() == "enabled"
-    // }
-    // fn is_domain(self, domain: &str) -> bool {
-    //     self.domain.to_lowercase() == domain.to_lowercase()
-    // }
-    fn is_owner(self, owner: &str) -> bool {
-        if owner.starts_with(">"){
-            self.owner.to_lowercase().ends_with(&owner[1..].to_lowercase())
-        } else if owner.starts_with("<"){
-            self.owner.to_lowercase().starts_with(&owner[1..].to_lowercase())
-        } else {
-            self.owner.to_lowercase() == owner.to_lowercase()
+        self.id.is_empty() && self.status.is_none() && self.domain.is_empty() && self.owner.is_empty() && self.created.is_none() && self.modified.is_none()
+    }
+    // Revisions that differ between AD and SYSVOL, labeled with the half of the GPO they belong to.
+    fn revision_mismatches(&self) -> Vec<String> {
+        let mut mismatches: Vec<String> = Vec::new();
+        for (label, revision) in [("Computer", &self.computer_revision), ("User", &self.user_revision)] {
+            if let Some(revision) = revision {
+                if !revision.is_consistent() {
+                    mismatches.push(format!("{} {}", label, revision));
+                }
+            }
+        }
+        mismatches
+    }
+    fn is_id(&self, id: &str) -> bool {
+        matches_value(&self.id, id)
+    }
+    fn is_domain(&self, domain: &str) -> bool {
+        matches_value(&self.domain, domain)
+    }
+    fn is_owner(&self, owner: &str) -> bool {
+        matches_value(&self.owner, owner)
+    }
+    // The named field as it is printed in a finding, i.e. ("Modified", "2019-05-01 10:00:00").
+    fn field(&self, name: &str) -> Option<(&'static str, String)> {
+        let date = |date: &Option<NaiveDateTime>| date.map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default();
+        match name.trim().to_lowercase().as_str() {
+            "owner" => Some(("Owner", self.owner.clone())),
+            "status" => Some(("Status", self.status.as_ref().map(|status| status.to_string()).unwrap_or_default())),
+            "domain" => Some(("Domain", self.domain.clone())),
+            "id" | "unique id" => Some(("ID", self.id.clone())),
+            "created" => Some(("Created", date(&self.created))),
+            "modified" => Some(("Modified", date(&self.modified))),
+            _ => None,
+        }
+    }
+    // Details::Field::Value. Created and Modified also take date comparisons and ages.
+    pub fn is_match(&self, field: &str, value: &str, now: &NaiveDateTime) -> bool {
+        let Some((name, current)) = self.field(field) else {
+            return false;
+        };
+        if let Some(stripped) = value.strip_prefix('!') {
+            return !self.is_match(field, stripped, now);
+        }
+        let date = match name {
+            "Created" => Some(self.created),
+            "Modified" => Some(self.modified),
+            _ => None,
+        };
+        if let Some(matched) = date.and_then(|date| matches_date(date, value, now)) {
+            return matched;
+        }
+        match name {
+            "Owner" => self.is_owner(value),
+            "Domain" => self.is_domain(value),
+            "ID" => self.is_id(value),
+            _ => matches_value(&current, value),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Link {
     pub location: String,
-    pub enforced: String,
-    pub status: String,
+    pub enforced: bool,
+    pub enabled: bool,
     pub path: String,
+    pub link_order: Option<u32>,
+    pub inheritance_blocked: bool,
+}
+
+// "Yes", "True" and "Enabled" are the affirmative values printed in the links table.
+fn is_affirmative(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+    value == "yes" || value == "true" || value == "enabled"
 }
 
 impl Link {
     fn new() -> Self {
         Self {
             location : String::new(),
-            enforced : String::new(),
-            status: String::new(),
-            path : String::new()
+            enforced : false,
+            enabled: true,
+            path : String::new(),
+            link_order: None,
+            inheritance_blocked: false,
         }
     }
     fn set_location(&mut self, value: &str) {
         self.location = value.to_string();
     }
     fn set_enforced(&mut self, value: &str) {
-        self.enforced = value.to_string();
+        self.enforced = is_affirmative(value);
     }
     fn set_status(&mut self, value: &str) {
-        self.status = value.to_string();
+        self.enabled = is_affirmative(value);
     }
     fn set_path(&mut self, value: &str) {
         self.path = value.to_string();
     }
-    fn is_location(self, value: &str) -> bool {
-        if value.starts_with(">") {
-            self.location.to_lowercase().ends_with(&value[1..].to_lowercase())
-        } else if value.starts_with("<") {
-            self.location.to_lowercase().starts_with(&value[1..].to_lowercase())
-        } else {
-            self.location.to_lowercase() == value.to_lowercase()
-        }
+    fn set_link_order(&mut self, value: &str) {
+        self.link_order = value.trim().parse::<u32>().ok();
+    }
+    fn set_inheritance_blocked(&mut self, value: &str) {
+        self.inheritance_blocked = is_affirmative(value);
+    }
+    fn is_location(&self, value: &str) -> bool {
+        matches_value(&self.location, value)
+    }
+    // States are "enforced", "enabled" and "blocked" (inheritance blocked), each negated with "!".
+    // A blank state matches any link.
+    fn is_state(&self, value: &str) -> bool {
+        let (negated, state) = match value.strip_prefix('!') {
+            Some(stripped) => (true, stripped),
+            None => (false, value),
+        };
+        let current = match state.trim().to_lowercase().as_str() {
+            "" => return true,
+            "enforced" => self.enforced,
+            "enabled" => self.enabled,
+            "disabled" => !self.enabled,
+            "blocked" | "inheritance blocked" => self.inheritance_blocked,
+            // Rejected by is_link_state when the query is parsed.
+            _ => return false,
+        };
+        current != negated
     }
-    // fn is_enforced(self, value: &str) -> bool {
-    //     self.enforced.to_lowercase() == value.to_lowercase()
-    // }
-    // fn is_status(self, value: &str) -> bool {
-    //     self.status.to_lowercase() == value.to_lowercase()
-    // }
-    // fn is_path(self, value: &str) -> bool {
-    //     self.path.to_lowercase() == value.to_lowercase()
-    // }
 }
 
-#[derive(Debug, Clone)]
+// Whether "value" is a state Link::is_state knows, i.e. "enforced" or "!blocked".
+pub fn is_link_state(value: &str) -> bool {
+    let state = value.trim();
+    let state = state.strip_prefix('!').unwrap_or(state);
+    ["", "enforced", "enabled", "disabled", "blocked", "inheritance blocked"].contains(&state.trim().to_lowercase().as_str())
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Delegation {
     pub name: String,
+    pub principal: Principal,
     pub permissions: Vec<String>,
     pub inherited: String,
 }
 
+impl Default for Delegation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Delegation {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
+            principal: Principal::parse(""),
             permissions: Vec::new(),
             inherited: "".to_string(),
         }
     }
     pub fn set_name(&mut self, new_name: &str){
         self.name = new_name.to_string();
+        self.principal = Principal::parse(new_name);
     }
     pub fn add_permission(&mut self, permission: &str){
         self.permissions.push(permission.to_string());
@@ -356,49 +724,64 @@ impl Delegation {
     pub fn set_inheritence(&mut self, inheritence: &str){
         self.inherited = inheritence.to_string();
     }
-    fn is_name(self, value: &str) -> bool {
-        if value.starts_with(">") {
-            self.name.to_lowercase().ends_with(&value[1..].to_lowercase())
-        }
-        else if value.starts_with("<") {
-            self.name.to_lowercase().starts_with(&value[1..].to_lowercase())
-        }
-        else {
-            self.name.to_lowercase() == value.to_lowercase()
-        }
+    fn is_name(&self, value: &str) -> bool {
+        self.principal.matches(value)
     }
-    fn contains_permission(self, value: &str) -> bool {
-        if value.starts_with(">"){
-            let iterator = self.permissions.iter();
-            for permission in iterator {
-                if permission.to_lowercase().ends_with(&value[1..].to_lowercase()){
-                    return true;
-                }
-            }
-            false
-        } 
-        else if value.starts_with("<"){
-            let iterator = self.permissions.iter();
-            for permission in iterator {
-                if permission.to_lowercase().starts_with(&value[1..].to_lowercase()){
-                    return true;
-                }
-            }
-            false
+    fn contains_permission(&self, value: &str) -> bool {
+        if let Some(stripped) = value.strip_prefix('!') {
+            return !self.contains_permission(stripped);
         }
-        else {
-            let iterator = self.permissions.iter();
-            for permission in iterator {
-                if permission.to_lowercase().contains(&value.to_lowercase()){
-                    return true;
-                }
-            }
-            false
+        if let Some(list) = ListQuery::parse(value) {
+            return list.is_match(&self.permissions, |permission, entry| matches_value(permission, entry));
+        }
+        // Without a modifier a permission only has to contain the value, i.e. "Edit settings".
+        let matcher = Matcher::parse(value).or_contains();
+        self.permissions.iter().any(|permission| matcher.is_match(permission))
+    }
+    // Permissions outside of a list operator, i.e. "Edit settings" for "only[Read]".
+    fn offending_permissions(&self, value: &str) -> Vec<String> {
+        match ListQuery::parse(value.strip_prefix('!').unwrap_or(value)) {
+            Some(list) => list.offending(&self.permissions, |permission, entry| matches_value(permission, entry)),
+            None => Vec::new(),
         }
     }
-    fn is_inherited(self) -> bool {
+    fn is_inherited(&self) -> bool {
         self.inherited.to_lowercase() == "yes"
     }
+    // "Read (from Security Filtering)" is Read and Apply Group Policy. Editing a GPO requires reading it.
+    fn grants_read(&self) -> bool {
+        self.permissions.iter().any(|permission| {
+            let permission = permission.to_lowercase();
+            permission.starts_with("read") || permission.starts_with("edit settings")
+        })
+    }
+    fn grants_apply(&self) -> bool {
+        self.permissions.iter().any(|permission| {
+            let permission = permission.to_lowercase();
+            permission.contains("from security filtering") || permission.contains("apply group policy")
+        })
+    }
+}
+
+// A principal's effective permissions over a GPO.
+#[derive(Debug, Clone)]
+pub struct FilterEntry {
+    pub principal: Principal,
+    pub read: bool,
+    pub apply: bool,
+}
+
+impl FilterEntry {
+    pub fn applies(&self) -> bool {
+        self.read && self.apply
+    }
+}
+
+impl fmt::Display for FilterEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |value: bool| if value { "Yes" } else { "No" };
+        write!(f, "Applies To: {} | Read: {} | Apply: {}", self.principal, yes_no(self.read), yes_no(self.apply))
+    }
 }
 
 impl fmt::Display for Delegation {
@@ -407,7 +790,7 @@ impl fmt::Display for Delegation {
         output_string += &format!("\tName: {} | Permissions: ", &self.name.as_str());
         for permission in self.permissions.clone() {
             output_string.push_str(&permission);
-            output_string.push_str(" ");
+            output_string.push(' ');
         }
         output_string += &format!("| Inherited {}\n", &self.inherited);
 
@@ -415,120 +798,127 @@ impl fmt::Display for Delegation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Policy {
     pub value: String,
-    pub setting: Vec<String>,
+    pub setting: String,
+    pub setting_value: SettingValue,
 }
 
 impl Policy {
     fn new() -> Self {
         Self {
             value: "".to_string(),
-            setting: Vec::new(),
+            setting: String::new(),
+            setting_value: SettingValue::NotDefined,
         }
     }
     fn set_value(&mut self, value: &str) {
         self.value = value.to_string();
     }
-    fn add_setting(&mut self, value: &str) {
-        self.setting.push(value.to_string());
+    // The raw cell is kept verbatim. Settings that are lists are also split into their members.
+    fn set_setting(&mut self, section: &str, value: &str) {
+        self.setting = value.to_string();
+        self.setting_value = SettingValue::parse_policy(section, &self.value, value);
     }
-    fn is_value(self, value: &str) -> bool {
-        if value.starts_with(">") {
-            self.value.to_lowercase().ends_with(&value[1..].to_lowercase())
+    fn is_value(&self, value: &str) -> bool {
+        matches_value(&self.value, value)
+    }
+    fn contains_setting(&self, value: &str) -> bool {
+        if let Some(list) = ListQuery::parse(value) {
+            return self.matches_list(&list);
         }
-        else if value.starts_with("<") {
-            self.value.to_lowercase().starts_with(&value[1..].to_lowercase())
+        if let Some(matched) = self.setting_value.matches_numeric(value) {
+            return matched;
         }
-        else {
-            self.value.to_lowercase() == value.to_lowercase()
+        if self.setting_value.principals().iter().any(|principal| principal.matches(value)) {
+            return true;
         }
-    }
-    fn contains_setting(self, value: &str) -> bool {
-        let iterator = self.setting.iter();
+        // Match the raw text or any member of a list.
+        let iterator = std::iter::once(&self.setting).chain(self.setting_value.items().iter());
         for setting in iterator {
-            if value.starts_with(">"){
-                if setting.to_lowercase().ends_with(&value[1..].to_lowercase()) {
-                    return true;
-                }
-            }
-            else if value.starts_with("<"){
-                if setting.to_lowercase().starts_with(&value[1..].to_lowercase()) {
-                    return true;
-                }
-            }
-            else if value.starts_with("#>=") && value[3..].parse::<i32>().is_ok(){
-                if setting.parse::<i32>().is_ok(){
-                    if value[3..].parse::<i32>().unwrap() < setting.parse::<i32>().unwrap() {
-                        return true;
-                    }
-                }
-            }
-            else if value.starts_with("#>") && value[2..].parse::<i32>().is_ok(){
-                if setting.parse::<i32>().is_ok(){
-                    if value[2..].parse::<i32>().unwrap() < setting.parse::<i32>().unwrap() {
-                        return true;
-                    }
-                }
-            }
-            else if value.starts_with("#<=") {
-                if setting.parse::<i32>().is_ok(){
-                    if value[3..].parse::<i32>().unwrap() >= setting.parse::<i32>().unwrap() {
-                        return true;
-                    }
-                }
-            }
-            else if value.starts_with("#<") {
-                if setting.parse::<i32>().is_ok(){
-                    if value[2..].parse::<i32>().unwrap() > setting.parse::<i32>().unwrap() {
-                        return true;
-                    }
-                }
-            }
-            else {
-                if setting.to_lowercase() == value.to_lowercase() {
-                    return true;
-                }
+            if matches_value(setting, value) {
+                return true;
             }
         }
         false
     }
 }
 
+impl Policy {
+    // A setting that isn't a list is a list of itself, unless it isn't defined.
+    fn matches_list(&self, list: &ListQuery) -> bool {
+        match &self.setting_value {
+            SettingValue::Principals(principals) => list.is_match(principals, |principal, entry| principal.matches(entry)),
+            SettingValue::List(items) => list.is_match(items, |item, entry| matches_value(item, entry)),
+            SettingValue::NotDefined => list.is_match(&Vec::<String>::new(), |item, entry| matches_value(item, entry)),
+            _ => list.is_match(std::slice::from_ref(&self.setting), |item, entry| matches_value(item, entry)),
+        }
+    }
+    fn offending_members(&self, value: &str) -> Vec<String> {
+        let Some(list) = ListQuery::parse(value) else {
+            return Vec::new();
+        };
+        match &self.setting_value {
+            SettingValue::Principals(principals) => list.offending(principals, |principal, entry| principal.matches(entry)),
+            SettingValue::List(items) => list.offending(items, |item, entry| matches_value(item, entry)),
+            SettingValue::NotDefined => list.offending(&Vec::<String>::new(), |item, entry| matches_value(item, entry)),
+            _ => list.offending(std::slice::from_ref(&self.setting), |item, entry| matches_value(item, entry)),
+        }
+    }
+}
+
 impl fmt::Display for Policy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output_string = String::new();
-        output_string += &format!("Policy: {} | Settings: ", &self.value.as_str());
-        for setting in self.setting.clone() {
-            output_string.push_str(&setting);
-            output_string.push_str(" ");
-        }
+        output_string += &format!("Policy: {} | Settings: {}", &self.value.as_str(), &self.setting.as_str());
 
         write!(f, "{}", output_string.trim_end())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct GroupPolicy {
     pub name: String,
     pub details: Details,
     pub links: Vec<Link>,
-    pub filtering: Vec<String>,
+    pub filtering: Vec<Principal>,
     pub delegation: Vec<Delegation>,
     pub policies: Vec<Policy>,
+    pub application_control: ApplicationControl,
+    pub firewall: Firewall,
+    pub public_key_policies: PublicKeyPolicies,
+    pub preferences: Preferences,
+    pub wmi_filter: Option<WmiFilter>,
+    pub coverage: Coverage,
 }
 
 impl fmt::Display for GroupPolicy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output_string = String::new();
-        output_string += &format!("\tName: {} | GPO Status: {}\n", &self.name.as_str(), &self.details.status);
+        let status = match &self.details.status {
+            Some(status) => status.to_string(),
+            None => String::new(),
+        };
+        output_string += &format!("\tName: {} | GPO Status: {}", &self.name.as_str(), status);
+        if let Some(modified) = self.details.modified {
+            output_string += &format!(" | Created: {}", self.details.created.map(|created| created.to_string()).unwrap_or_default());
+            output_string += &format!(" | Modified: {}", modified);
+        }
+        output_string += "\n";
         let mut links_string = String::new();
         for link in self.links.iter() {
             links_string.push_str(link.to_string().as_str());
-            links_string.push_str(" ");
+            links_string.push(' ');
         }
         output_string += &format!("\tLinks: [ {} ]", &links_string.trim_end());
+        if let Some(filter) = &self.wmi_filter {
+            output_string += &format!("\n\t{}", filter);
+        }
+        let mismatches = self.details.revision_mismatches();
+        if !mismatches.is_empty() {
+            output_string += &format!("\n\tRevision Mismatch: {}", mismatches.join(" | "));
+        }
 
         write!(f, "{}", output_string)
     }
@@ -538,7 +928,16 @@ impl fmt::Display for Link {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output_string = String::new();
         output_string += &format!("L:{}:", &self.location.as_str());
-        output_string += &format!("S:{} ", &self.status.as_str());
+        output_string += &format!("S:{}", if self.enabled { "Enabled" } else { "Disabled" });
+        if self.enforced {
+            output_string += ":Enforced";
+        }
+        if self.inheritance_blocked {
+            output_string += ":Blocked";
+        }
+        if let Some(order) = self.link_order {
+            output_string += &format!(":O:{}", order);
+        }
 
         write!(f, "{}", output_string.trim_end())
     }
@@ -550,6 +949,7 @@ impl GroupPolicy {
         let mut f = File::open(group_policy_txt)?;
         let mut content = String::new();
         File::read_to_string(&mut f, &mut content)?;
+        let content = table::unwrap_rows(&content);
 
         let mut name = String::new();
         let mut name_block = true;
@@ -557,29 +957,37 @@ impl GroupPolicy {
         let mut details_block = false;
         let mut links: Vec<Link> = Vec::new();
         let mut links_block = false;
-        let mut filtering: Vec<String> = Vec::new();
+        let mut links_header: Vec<String> = Vec::new();
+        let mut filtering: Vec<Principal> = Vec::new();
         let mut filtering_block = 0;
         let mut delegation: Vec<Delegation> = Vec::new();
         let mut delegation_block = 0;
         let mut policies: Vec<Policy> = Vec::new();
         let mut policy_block = false;
+        let mut policy_section = String::new();
+        // Line numbers of the rows read below, for the coverage report.
+        let mut parsed: HashSet<usize> = HashSet::new();
 
-        for line in content.lines(){
+        for (index, line) in content.lines().enumerate(){
             
             // Name Parsing Logic
             if name_block && name.is_empty() && !line.is_empty() && !line.contains("────"){
                 name = line.trim_start().trim_end().to_string();
                 name_block = false;
+                parsed.insert(index);
             }
 
             // Detail Parsing Logic
-            if line.starts_with("Details") && details_block == false && details.is_empty(){
+            if line.starts_with("Details") && !details_block && details.is_empty(){
                 details_block = true;
             }
             else if details_block && line.is_empty() && !details.is_empty() {
                 details_block = false;
             }
             else if details_block && !line.is_empty() {
+                if DETAILS_ROWS.iter().any(|row| line.starts_with(row)) {
+                    parsed.insert(index);
+                }
                 if line.starts_with("Domain"){
                     let split_line: Vec<&str> = line.split("│").collect();
                     details.set_domain(split_line[1].trim_end().to_string());
@@ -604,24 +1012,50 @@ impl GroupPolicy {
                     let split_line: Vec<&str> = line.split("│").collect();
                     details.set_status(split_line[1].trim_end().to_string());
                 }
+                else if line.starts_with("Computer Revision") || line.starts_with("Computer Version"){
+                    let split_line: Vec<&str> = line.split("│").collect();
+                    details.set_computer_revision(split_line[1].trim_end().to_string());
+                }
+                else if line.starts_with("User Revision") || line.starts_with("User Version"){
+                    let split_line: Vec<&str> = line.split("│").collect();
+                    details.set_user_revision(split_line[1].trim_end().to_string());
+                }
             }
 
             // Link Parsing Logic
-            if line.starts_with("Links") && links_block == false && links.is_empty(){
+            if line.starts_with("Links") && !links_block && links.is_empty(){
                 links_block = true;
             }
             else if links_block && line.is_empty() && !links.is_empty() {
                 links_block = false;
             }
-            else if links_block && !line.is_empty() && !line.contains("────") && !line.starts_with("Location") {
+            else if links_block && line.starts_with("Location") {
+                links_header = table::split_row(line);
+                parsed.insert(index);
+            }
+            else if links_block && !line.is_empty() && !line.contains("────") {
                 let mut curr_link: Link = Link::new();
-                let line_values: Vec<&str> = line.split("│").collect();
-                if line_values.len() == 4 {
-                    curr_link.set_location(&line_values[0].trim_end());
-                    curr_link.set_enforced(&line_values[1].trim_end());
-                    curr_link.set_status(&line_values[2].trim_end());
-                    curr_link.set_path(&line_values[3].trim_end());
+                let line_values = table::split_row(line);
+                if !links_header.is_empty() && line_values.len() == links_header.len() {
+                    // Link order and block inheritance are only printed by some report versions.
+                    curr_link.set_location(&table::cell(&line_values, table::column(&links_header, &["Location"])));
+                    curr_link.set_enforced(&table::cell(&line_values, table::column(&links_header, &["Enforced"])));
+                    if let Some(index) = table::column(&links_header, &["Link Status", "Link Enabled", "Status"]) {
+                        curr_link.set_status(&line_values[index]);
+                    }
+                    curr_link.set_path(&table::cell(&line_values, table::column(&links_header, &["Path"])));
+                    curr_link.set_link_order(&table::cell(&line_values, table::column(&links_header, &["Link Order", "Order"])));
+                    curr_link.set_inheritance_blocked(&table::cell(&line_values, table::column(&links_header, &["Block Inheritance", "Inheritance Blocked"])));
                     links.push(curr_link);
+                    parsed.insert(index);
+                }
+                else if line_values.len() == 4 {
+                    curr_link.set_location(&line_values[0]);
+                    curr_link.set_enforced(&line_values[1]);
+                    curr_link.set_status(&line_values[2]);
+                    curr_link.set_path(&line_values[3]);
+                    links.push(curr_link);
+                    parsed.insert(index);
                 }
             }
 
@@ -633,6 +1067,7 @@ impl GroupPolicy {
             // Find the table header
             else if filtering_block == 1 && line.starts_with("Name") {
                 filtering_block = 2;
+                parsed.insert(index);
             }
             // After leaving the table
             else if filtering_block == 2 && (line.is_empty() || line.starts_with("Delegation")) && !filtering.is_empty() {
@@ -640,7 +1075,8 @@ impl GroupPolicy {
             }
             // Items of the table
             else if filtering_block == 2 && !line.is_empty() && !line.contains("────") {
-                filtering.push(line.to_string());
+                filtering.push(Principal::parse(line));
+                parsed.insert(index);
             }
 
             // Delegation parsing logic
@@ -649,6 +1085,7 @@ impl GroupPolicy {
             }
             else if delegation_block == 1 && line.starts_with("Name") {
                 delegation_block = 2;
+                parsed.insert(index);
             }
             else if delegation_block == 2 && (line.is_empty() || line.starts_with("User Configuration") || line.starts_with("Computer Configuration")) && !delegation.is_empty() {
                 delegation_block = 0;
@@ -668,12 +1105,14 @@ impl GroupPolicy {
                     new_delegation.set_inheritence(delegation_interited.trim_end());
 
                     delegation.push(new_delegation);
+                    parsed.insert(index);
                 }
             }
 
             // Policy parsing logic
-            if line.starts_with("Policy") && policy_block == false {
+            if line.starts_with("Policy") && !policy_block {
                 policy_block = true;
+                parsed.insert(index);
             }
             else if policy_block && line.is_empty() {
                 policy_block = false;
@@ -682,38 +1121,54 @@ impl GroupPolicy {
                 let mut new_policy = Policy::new();
                 let full_policy_string:Vec<&str> = line.split("│").collect();
                 new_policy.set_value(full_policy_string[0].trim_start().trim_end());
-                for setting in full_policy_string[1].split(","){
-                    new_policy.add_setting(setting.trim_start().trim_end());
-                }
+                new_policy.set_setting(&policy_section, full_policy_string[1].trim_start().trim_end());
                 policies.push(new_policy);
+                parsed.insert(index);
+            }
+            // The heading above a policy table, i.e. "Local Policies/User Rights Assignment".
+            else if !policy_block && !line.trim().is_empty() && !table::is_row(line) && !table::is_border(line) {
+                policy_section = line.trim().to_string();
             }
         }
 
-        Ok(Self {
-            name: name,
-            details: details,
-            links: links,
-            filtering: filtering,
-            delegation: delegation,
-            policies: policies,
-        })
+        let mut group_policy = Self {
+            name,
+            details,
+            links,
+            filtering,
+            delegation,
+            policies,
+            application_control: ApplicationControl::parse_lines(&content, &mut parsed),
+            firewall: Firewall::parse_lines(&content, &mut parsed),
+            public_key_policies: PublicKeyPolicies::parse_lines(&content, &mut parsed),
+            preferences: Preferences::parse_lines(&content, &mut parsed),
+            wmi_filter: WmiFilter::parse_lines(&content, &mut parsed),
+            coverage: Coverage::default(),
+        };
+        group_policy.coverage = Coverage::new(&content, &parsed);
+
+        Ok(group_policy)
+    }
+
+    // No settings of any kind were parsed.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+            && self.application_control.enforcement.is_empty()
+            && self.application_control.applocker_rules.is_empty()
+            && self.application_control.srp_rules.is_empty()
+            && self.firewall.profiles.is_empty()
+            && self.firewall.rules.is_empty()
+            && self.public_key_policies.certificates.is_empty()
+            && self.public_key_policies.auto_enrollment.is_empty()
+            && self.preferences.items.is_empty()
     }
 
     // Name
     fn is_name(&self, name: &str) -> bool {
-        self.name.to_lowercase() == name.to_lowercase()
+        matches_value(&self.name, name)
     }
 
     // Details
-    // fn is_id(&self, id: &str) -> bool {
-    //     self.details.clone().is_id(id)
-    // }
-    // fn is_enabled(&self) -> bool {
-    //     self.details.clone().is_enabled()
-    // }
-    // fn is_domain(&self, domain: &str) -> bool {
-    //     self.details.clone().is_domain(domain)
-    // }
     fn is_owner(&self, owner: &str) -> bool {
         self.details.clone().is_owner(owner)
     }
@@ -729,40 +1184,45 @@ impl GroupPolicy {
         -1
     }
 
-    // fn is_link_active(&self, value: &str) -> bool {
-    //     let link_to_check = self.contains_link_location(value);
-
-    //     if link_to_check != -1 {
-    //         let link_clone = self.links[link_to_check as usize].clone();
-    //         link_clone.is_status("Enabled")
-    //     } else {
-    //         false
-    //     }
-    // }
+    fn contains_link_state(&self, location: &str, state: &str) -> bool {
+        self.links.iter().any(|link| link.is_location(location) && link.is_state(state))
+    }
 
     // Filtering
     fn contains_filter(&self, value: &str) -> bool {
         let iterator = self.filtering.iter();
 
         for filter in iterator {
-            if value.starts_with(">"){
-                if filter.to_lowercase().ends_with(&value[1..].to_lowercase()){
-                    return true;
-                }
-            }
-            else if value.starts_with("<"){
-                if filter.to_lowercase().starts_with(&value[1..].to_lowercase()){
-                    return true;
-                }
-            } else {
-                if filter.to_lowercase() == value.to_lowercase() {
-                    return true;
-                }
+            if filter.matches(value) {
+                return true;
             }
         }
         false
     }
 
+    // Every principal named by Security Filtering or Delegation with the Read and Apply Group Policy
+    // permissions it effectively holds. A GPO only applies to principals with both.
+    fn security_filter(&self) -> Vec<FilterEntry> {
+        let mut entries: Vec<FilterEntry> = Vec::new();
+        let principals = self.filtering.iter().chain(self.delegation.iter().map(|delegation| &delegation.principal));
+        for principal in principals {
+            if entries.iter().any(|entry| entry.principal.is_equivalent(principal)) {
+                continue;
+            }
+            let delegations: Vec<&Delegation> = self.delegation.iter().filter(|delegation| delegation.principal.is_equivalent(principal)).collect();
+            entries.push(FilterEntry {
+                principal: principal.clone(),
+                read: delegations.iter().any(|delegation| delegation.grants_read()),
+                apply: self.filtering.iter().any(|filter| filter.is_equivalent(principal)) || delegations.iter().any(|delegation| delegation.grants_apply()),
+            });
+        }
+        entries
+    }
+
+    fn applies_to(&self, value: &str) -> bool {
+        self.security_filter().iter().any(|entry| entry.applies() && entry.principal.matches(value))
+    }
+
     // Delegations
     fn contains_delegation_name(&self, value: &str) -> i32 {
         let iterator = self.delegation.iter();
@@ -776,7 +1236,7 @@ impl GroupPolicy {
     }
 
     fn contains_delegation_permission(&self, delegation_name: &str, delegation_permission: &str) -> bool {
-        let del_index = self.contains_delegation_name(&delegation_name);
+        let del_index = self.contains_delegation_name(delegation_name);
         if del_index != -1 {
             let delegation_clone: Delegation = self.delegation[del_index as usize].clone();
             delegation_clone.contains_permission(delegation_permission)
@@ -786,7 +1246,7 @@ impl GroupPolicy {
     }
 
     fn is_delegation_inherited(&self, delegation_name: &str) -> bool {
-        let perm_index = self.contains_delegation_name(&delegation_name);
+        let perm_index = self.contains_delegation_name(delegation_name);
         if perm_index != -1 {
             let delegation_clone: Delegation = self.delegation[perm_index as usize].clone();
             delegation_clone.is_inherited()
@@ -800,7 +1260,7 @@ impl GroupPolicy {
         let iterator = self.policies.iter();
 
         for (index, policy) in iterator.enumerate() {
-            if policy.clone().is_value(&policy_value){
+            if policy.clone().is_value(policy_value){
                 return index as i32
             }
         }
@@ -818,110 +1278,159 @@ impl GroupPolicy {
         }
     }
 
-    pub fn query_gpo(&self, gpo_query_string: &str) -> bool {
-        let without_comment: Vec<&str> = gpo_query_string.split("//").collect();
-        if !without_comment[0].trim_start().trim_end().is_empty(){
-            if gpo_query_string.to_lowercase().starts_with("name") && gpo_query_string.split("::").count() == 2 {
+    // Parse a single condition and match it, i.e. "Policy::Minimum password length::#<14".
+    pub fn query_gpo(&self, gpo_query_string: &str, now: &NaiveDateTime) -> bool {
+        match Condition::parse(gpo_query_string) {
+            Ok(condition) => self.evaluate(&condition, now),
+            Err(_) => false,
+        }
+    }
+
+    // "now" is the time relative dates and certificate expiry are checked against.
+    pub fn evaluate(&self, condition: &Condition, now: &NaiveDateTime) -> bool {
+        let values: Vec<&str> = condition.values.iter().map(|value| value.as_str()).collect();
+        match (condition.field, values.len()) {
+            (Field::Name, 1) => {
                 // Name:Value
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let name_to_check = split_values[1];
-                self.is_name(&name_to_check)
+                let name_to_check = values[0];
+                self.is_name(name_to_check)
             }
-            else if gpo_query_string.to_lowercase().starts_with("details") && gpo_query_string.split("::").count() == 2 {
+            (Field::Details, 1) => {
                 // Details:Owner
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let owner = split_values[1];
-                if owner.starts_with("!"){
-                    !self.is_owner(&owner[1..])
+                let owner = values[0];
+                if let Some(stripped) = owner.strip_prefix('!') {
+                    !self.is_owner(stripped)
                 } else {
-                    self.is_owner(&owner)
+                    self.is_owner(owner)
                 }
             }
-            else if gpo_query_string.to_lowercase().starts_with("links") && gpo_query_string.split("::").count() == 2 {
+            (Field::Details, 2) => {
+                // Details:Field:Value
+                self.details.is_match(values[0], values[1], now)
+            }
+            (Field::Links, 1) => {
                 // Links:location
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let location = split_values[1];
-                let location_index = self.contains_link_location(&location);
-                return if location_index != -1 { true } else { false };
+                let location = values[0];
+                let location_index = self.contains_link_location(location);
+                location_index != -1
+            }
+            (Field::Links, 2) => {
+                // Links:location:State
+                self.contains_link_state(values[0], values[1])
             }
-            else if gpo_query_string.to_lowercase().starts_with("filtering") && gpo_query_string.split("::").count() == 2 {
+            (Field::Filtering, 1) => {
                 // Filtering:Value
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let filter = split_values[1];
-                self.contains_filter(&filter)
+                let filter = values[0];
+                self.contains_filter(filter)
+            }
+            (Field::Applies, 1) => {
+                // Applies:Principal
+                let principal = values[0];
+                if let Some(stripped) = principal.strip_prefix('!') {
+                    !self.applies_to(stripped)
+                } else {
+                    self.applies_to(principal)
+                }
             }
-            else if gpo_query_string.to_lowercase().starts_with("delegation") && gpo_query_string.split("::").count() == 4 {
+            (Field::Delegation, 3) => {
                 // Delegation:Name:Permissions:Inherited
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let name = split_values[1];
-                let permission = split_values[2];
-                let inherited = split_values[3];
+                let name = values[0];
+                let permission = values[1];
+                let inherited = values[2];
     
-                (!name.is_empty() && !permission.is_empty() && self.contains_delegation_permission(&name, &permission)) && (inherited.is_empty() || if inherited.to_lowercase() == "yes" { self.is_delegation_inherited(&name) } else { !self.is_delegation_inherited(&name) })
+                (!name.is_empty() && !permission.is_empty() && self.contains_delegation_permission(name, permission)) && (inherited.is_empty() || if inherited.to_lowercase() == "yes" { self.is_delegation_inherited(name) } else { !self.is_delegation_inherited(name) })
             }
-            else if gpo_query_string.to_lowercase().starts_with("policy") && gpo_query_string.split("::").count() == 3 {
+            (Field::Policy, 2) => {
                 // Policy:Value:Setting
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let value = split_values[1];
-                let setting = split_values[2];
-                if setting.starts_with("!"){
-                    self.contains_policy(&value) != -1 && !self.contains_policy_setting(&value, &setting[1..])
+                let value = values[0];
+                let setting = values[1];
+                if let Some(stripped) = setting.strip_prefix('!') {
+                    self.contains_policy(value) != -1 && !self.contains_policy_setting(value, stripped)
                 } else {
-                    self.contains_policy_setting(&value, &setting) || (setting.is_empty() && if self.contains_policy(&value) != -1 { true } else { false })
+                    self.contains_policy_setting(value, setting) || (setting.is_empty() && self.contains_policy(value) != -1)
                 }
             }
-            else {
-                false
+            (Field::AppLocker, 4) => {
+                // AppLocker:Collection:Action:User:Condition
+                self.application_control.applocker_rules.iter().any(|rule| rule.is_match(values[0], values[1], values[2], values[3]))
+            }
+            (Field::SoftwareRestriction, 2) => {
+                // SoftwareRestriction:Rule:SecurityLevel
+                self.application_control.srp_rules.iter().any(|rule| rule.is_match(values[0], values[1]))
+            }
+            (Field::FirewallRule, 4) => {
+                // FirewallRule:Direction:Action:Field:Value
+                self.firewall.rules.iter().any(|rule| rule.is_match(values[0], values[1], values[2], values[3]))
             }
+            (Field::Firewall, 3) => {
+                // Firewall:Profile:Setting:Value
+                self.firewall.profiles.iter().any(|profile| profile.is_match(values[0], values[1], values[2]))
+            }
+            (Field::Certificate, 3) => {
+                // Certificate:Store:Field:Value
+                self.public_key_policies.certificates.iter().any(|certificate| certificate.is_match(values[0], values[1], values[2], now))
+            }
+            (Field::Preference, 3) => {
+                // Preference:Category:Field:Value
+                self.preferences.items.iter().any(|item| item.is_match(values[0], values[1], values[2]))
+            }
+            (Field::Wmi, 2) => {
+                // WMI:Field:Value
+                match &self.wmi_filter {
+                    Some(filter) => filter.is_match(values[0], values[1]),
+                    None => false,
+                }
+            }
+            _ => false,
         }
-        else {
-            false
+    }
+
+    pub fn get_matching_conditions(&self, gpo_query_string: &str, now: &NaiveDateTime) -> String {
+        match Condition::parse(gpo_query_string) {
+            Ok(condition) => self.matching_values(&condition, now),
+            Err(_) => String::new(),
         }
     }
 
-    pub fn get_matching_conditions(&self, gpo_query_string: &str) -> String {
+    // The values that satisfied a condition, for the analysis output.
+    pub fn matching_values(&self, condition: &Condition, now: &NaiveDateTime) -> String {
         let mut match_string = String::new();
-        let without_comment: Vec<&str> = gpo_query_string.split("//").collect();
-        if !without_comment[0].trim_start().trim_end().is_empty(){
-            if gpo_query_string.to_lowercase().starts_with("name") && gpo_query_string.split("::").count() == 2 {
+        let values: Vec<&str> = condition.values.iter().map(|value| value.as_str()).collect();
+        match (condition.field, values.len()) {
+            (Field::Name, 1) => {
                 // Name:Value
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let name_to_check = split_values[1];
-                if self.is_name(&name_to_check) {
+                let name_to_check = values[0];
+                if self.is_name(name_to_check) {
                     match_string.push_str(self.name.as_str());
                 }
                 match_string.trim_start().trim_end().to_string()
             }
-            else if gpo_query_string.to_lowercase().starts_with("details") && gpo_query_string.split("::").count() == 2 {
+            (Field::Details, 1) => {
                 // Details:Owner
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let owner = split_values[1];
-                if owner.starts_with("!"){
-                    if !self.is_owner(&owner[1..]) {
+                let owner = values[0];
+                if let Some(stripped) = owner.strip_prefix('!') {
+                    if !self.is_owner(stripped) {
                         match_string.push_str(&self.details.owner);
                     }
                     match_string.trim_start().trim_end().to_string()
                 } else {
-                    if self.is_owner(&owner) {
+                    if self.is_owner(owner) {
                         match_string.push_str(&self.details.owner);
                     }
                     match_string.trim_start().trim_end().to_string()
                 }
             }
-            else if gpo_query_string.to_lowercase().starts_with("links") && gpo_query_string.split("::").count() == 2 {
+            (Field::Details, 2) => {
+                // Details:Field:Value
+                if let Some((name, current)) = self.details.field(values[0]).filter(|_| self.details.is_match(values[0], values[1], now)) {
+                    match_string.push_str(&format!("{}: {}", name, current));
+                }
+                match_string
+            }
+            (Field::Links, 1) => {
                 // Links:location
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let location = split_values[1];
-                let location_index = self.contains_link_location(&location);
+                let location = values[0];
+                let location_index = self.contains_link_location(location);
                 if location_index != -1 {
                     match_string.push_str(&self.links[location_index as usize].to_string());
                     match_string.trim_start().trim_end().to_string()
@@ -929,69 +1438,101 @@ impl GroupPolicy {
                     match_string.trim_start().trim_end().to_string()
                 }
             }
-            else if gpo_query_string.to_lowercase().starts_with("filtering") && gpo_query_string.split("::").count() == 2 {
+            (Field::Links, 2) => {
+                // Links:location:State
+                for link in self.links.iter() {
+                    if link.is_location(values[0]) && link.is_state(values[1]) {
+                        match_string.push_str(&link.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Filtering, 1) => {
                 // Filtering:Value
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let filter = split_values[1];
-                for gpo_filter in self.filtering.clone() {
-                    if gpo_filter.contains(&filter){
-                        match_string.push_str(&gpo_filter);
+                let filter = values[0];
+                for gpo_filter in self.filtering.iter() {
+                    if gpo_filter.matches(filter){
+                        match_string.push_str(&gpo_filter.raw);
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Applies, 1) => {
+                // Applies:Principal
+                let principal = values[0];
+                let entries = self.security_filter();
+                if let Some(stripped) = principal.strip_prefix('!') {
+                    if !self.applies_to(stripped) {
+                        for entry in entries.iter().filter(|entry| entry.principal.matches(stripped)) {
+                            match_string.push_str(&entry.to_string());
+                            match_string.push_str("\n\t");
+                        }
+                    }
+                } else {
+                    for entry in entries.iter().filter(|entry| entry.applies() && entry.principal.matches(principal)) {
+                        match_string.push_str(&entry.to_string());
+                        match_string.push_str("\n\t");
                     }
                 }
                 match_string.trim_start().trim_end().to_string()
             }
-            else if gpo_query_string.to_lowercase().starts_with("delegation") && gpo_query_string.split("::").count() == 4 {
+            (Field::Delegation, 3) => {
                 // Delegation:Name:Permissions:Inherited
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let name = split_values[1];
-                let permission = split_values[2];
-                let inherited = split_values[3];
+                let name = values[0];
+                let permission = values[1];
+                let inherited = values[2];
 
                 for deleg in self.delegation.clone() {
                     if !name.is_empty() {
                         if permission.is_empty() && inherited.is_empty() {
                             if deleg.clone().is_name(name) {
                                 match_string.push_str(&deleg.to_string());
-                                match_string.push_str("\n");
+                                match_string.push('\n');
                             }
                         }
                         else if inherited.is_empty() {
                             if deleg.clone().is_name(name) && deleg.clone().contains_permission(permission) {
                                 match_string.push_str(&deleg.to_string());
-                                match_string.push_str("\n");
+                                match_string.push('\n');
                             }
                         } else {
                             if deleg.clone().is_name(name) && deleg.clone().contains_permission(permission) && deleg.clone().inherited == inherited {
                                 match_string.push_str(&deleg.to_string());
-                                match_string.push_str("\n");
+                                match_string.push('\n');
                             }
                         }
+                        // A negated list operator, i.e. "!only[Read]", lists the permissions outside of it.
+                        let offending = deleg.offending_permissions(permission);
+                        if deleg.is_name(name) && !offending.is_empty() {
+                            match_string.push_str(&format!("\tOffending: {}\n", offending.join(", ")));
+                        }
                     }
                 }
                 match_string.trim_start().trim_end().to_string()
             }
-            else if gpo_query_string.to_lowercase().starts_with("policy") && gpo_query_string.split("::").count() == 3 {
+            (Field::Policy, 2) => {
                 // Policy:Value:Setting
-                let commentless:Vec<&str> = gpo_query_string.split("//").collect();
-                let split_values: Vec<&str> = commentless[0].trim_start().trim_end().split("::").collect();
-                let value = split_values[1];
-                let setting = split_values[2];
-                if setting.starts_with("!"){
-                    if self.contains_policy(&value) != -1 && 
-                    !self.contains_policy_setting(&value, &setting[1..]) {
+                let value = values[0];
+                let setting = values[1];
+                if let Some(stripped) = setting.strip_prefix('!') {
+                    if self.contains_policy(value) != -1 && 
+                    !self.contains_policy_setting(value, stripped) {
                         for policy in self.policies.clone() {
-                            if policy.clone().is_value(value) && !policy.clone().contains_setting(setting){
+                            if policy.clone().is_value(value) && !policy.clone().contains_setting(stripped){
                                 match_string.push_str(&policy.to_string());
+                                let offending = policy.offending_members(stripped);
+                                if !offending.is_empty() {
+                                    match_string.push_str(&format!(" | Offending: {}", offending.join(", ")));
+                                }
                                 match_string.push_str("\n\t");
                             }
                         }
                     }
                     match_string.trim_start().trim_end().to_string()
                 } else {
-                    if self.contains_policy_setting(&value, &setting) ||
-                    setting.is_empty() && self.contains_policy(&value) != -1 {
+                    if self.contains_policy_setting(value, setting) ||
+                    setting.is_empty() && self.contains_policy(value) != -1 {
                         for policy in self.policies.clone() {
                             if policy.clone().is_value(value) && policy.clone().contains_setting(setting){
                                 match_string.push_str(&policy.to_string());
@@ -1002,12 +1543,76 @@ impl GroupPolicy {
                     match_string.trim_start().trim_end().to_string()
                 }
             }
-            else {
+            (Field::AppLocker, 4) => {
+                // AppLocker:Collection:Action:User:Condition
+                for rule in self.application_control.applocker_rules.iter() {
+                    if rule.is_match(values[0], values[1], values[2], values[3]) {
+                        match_string.push_str(&rule.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
                 match_string.trim_start().trim_end().to_string()
             }
-        }
-        else {
-            String::new()
+            (Field::SoftwareRestriction, 2) => {
+                // SoftwareRestriction:Rule:SecurityLevel
+                for rule in self.application_control.srp_rules.iter() {
+                    if rule.is_match(values[0], values[1]) {
+                        match_string.push_str(&rule.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::FirewallRule, 4) => {
+                // FirewallRule:Direction:Action:Field:Value
+                for rule in self.firewall.rules.iter() {
+                    if rule.is_match(values[0], values[1], values[2], values[3]) {
+                        match_string.push_str(&rule.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Firewall, 3) => {
+                // Firewall:Profile:Setting:Value
+                for profile in self.firewall.profiles.iter() {
+                    if profile.is_match(values[0], values[1], values[2]) {
+                        match_string.push_str(&profile.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Certificate, 3) => {
+                // Certificate:Store:Field:Value
+                for certificate in self.public_key_policies.certificates.iter() {
+                    if certificate.is_match(values[0], values[1], values[2], now) {
+                        match_string.push_str(&certificate.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Preference, 3) => {
+                // Preference:Category:Field:Value
+                for item in self.preferences.items.iter() {
+                    if item.is_match(values[0], values[1], values[2]) {
+                        match_string.push_str(&item.to_string());
+                        match_string.push_str("\n\t");
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            (Field::Wmi, 2) => {
+                // WMI:Field:Value
+                if let Some(filter) = &self.wmi_filter {
+                    if filter.is_match(values[0], values[1]) {
+                        match_string.push_str(&filter.to_string());
+                    }
+                }
+                match_string.trim_start().trim_end().to_string()
+            }
+            _ => String::new(),
         }
     }
 }
@@ -1015,6 +1620,13 @@ impl GroupPolicy {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
+
+    // Queries are checked at a fixed time so relative dates don't depend on the clock.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
     fn gen_empty_gpo() -> GroupPolicy {
         GroupPolicy {
             name: "".to_string(),
@@ -1023,6 +1635,12 @@ mod tests {
             filtering: Vec::new(),
             delegation: Vec::new(),
             policies: Vec::new(),
+            application_control: ApplicationControl::new(),
+            firewall: Firewall::new(),
+            public_key_policies: PublicKeyPolicies::new(),
+            preferences: Preferences::new(),
+            wmi_filter: None,
+            coverage: Coverage::default(),
         }
     }
 
@@ -1031,35 +1649,48 @@ mod tests {
         let mut test_gpo = gen_empty_gpo();
         test_gpo.name = "TEST GPO".to_string();
 
-        assert_eq!(test_gpo.is_name("TEST GPO"), true);
-        assert_ne!(test_gpo.is_name("GPO TEST"), true);
+        assert!(test_gpo.is_name("TEST GPO"));
+        assert!(!test_gpo.is_name("GPO TEST"));
     }
 
-    // #[test]
-    // fn test_is_id(){
-    //     let mut test_gpo = gen_empty_gpo();
-    //     test_gpo.details.set_id("af0a9f3d-0143-4721-9c82-570172cf71c3".to_string());
-
-    //     assert_eq!(test_gpo.is_id("af0a9f3d-0143-4721-9c82-570172cf71c3"), true);
-    //     assert_ne!(test_gpo.is_id("id"), true);
-    // }
+    #[test]
+    fn test_is_id(){
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.details.set_id("af0a9f3d-0143-4721-9c82-570172cf71c3".to_string());
 
-    // #[test]
-    // fn test_is_enabled(){
-    //     let mut test_gpo = gen_empty_gpo();
-    //     test_gpo.details.set_status("enabled".to_string());
+        assert!(test_gpo.details.is_id("af0a9f3d-0143-4721-9c82-570172cf71c3"));
+        assert!(!test_gpo.details.is_id("id"));
+    }
 
-    //     assert_eq!(test_gpo.is_enabled(), true);
-    // }
+    #[test]
+    fn test_is_domain(){
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.details.set_domain("labs.local".to_string());
 
-    // #[test]
-    // fn test_is_domain(){
-    //     let mut test_gpo = gen_empty_gpo();
-    //     test_gpo.details.set_domain("labs.local".to_string());
+        assert!(test_gpo.details.is_domain("labs.local"));
+        assert!(!test_gpo.details.is_domain("local.labs"));
+    }
 
-    //     assert_eq!(test_gpo.is_domain("labs.local"), true);
-    //     assert_ne!(test_gpo.is_domain("local.labs"), true);
-    // }
+    #[test]
+    fn test_details_query(){
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.details.set_status("User configuration settings disabled".to_string());
+        test_gpo.details.set_domain("corp.local".to_string());
+        test_gpo.details.set_created("3/1/2012 9:30:00 AM".to_string());
+        test_gpo.details.set_modified("3/1/2019 9:30:00 AM".to_string());
+
+        assert!(test_gpo.query_gpo("Details::Status::!Enabled", &now()));
+        assert!(test_gpo.query_gpo("Details::Status::<User", &now()));
+        assert!(test_gpo.query_gpo("Details::Domain::corp.local", &now()));
+        assert!(test_gpo.query_gpo("Details::Modified::<2020-01-01", &now()));
+        assert!(!test_gpo.query_gpo("Details::Modified::>=2020-01-01", &now()));
+        assert!(test_gpo.query_gpo("Details::Modified::older than 3 years", &now()));
+        assert!(test_gpo.query_gpo("Details::Created::!newer than 10 years", &now()));
+        assert!(test_gpo.query_gpo("Details::Created::<2012-03-02", &now()));
+        assert!(!test_gpo.query_gpo("Details::ID::!", &now()));
+        assert!(!test_gpo.query_gpo("Details::Revision::!Enabled", &now()));
+        assert_eq!(test_gpo.get_matching_conditions("Details::Modified::older than 3 years", &now()), "Modified: 2019-03-01 09:30:00");
+    }
 
     #[test]
     fn test_is_owner(){
@@ -1067,11 +1698,11 @@ mod tests {
         test_gpo.details.set_owner("LABS\\Domain Admins".to_string());
 
         // Test Equals
-        assert_eq!(test_gpo.is_owner("LABS\\Domain Admins"), true);
+        assert!(test_gpo.is_owner("LABS\\Domain Admins"));
         // Test Ends With
-        assert_eq!(test_gpo.is_owner(">Domain Admins"), true);
+        assert!(test_gpo.is_owner(">Domain Admins"));
         // Test Starts With
-        assert_eq!(test_gpo.is_owner("<LABS"), true);
+        assert!(test_gpo.is_owner("<LABS"));
     }
 
     #[test]
@@ -1080,9 +1711,11 @@ mod tests {
         test_gpo.links.push(
             Link {
                 location: "Domain Controllers".to_string(),
-                enforced: "No".to_string(),
-                status: "Enabled".to_string(),
+                enforced: false,
+                enabled: true,
                 path: "labs.local/Domain Controllers".to_string(),
+                link_order: Some(1),
+                inheritance_blocked: false,
             }
         );
 
@@ -1090,35 +1723,39 @@ mod tests {
         assert_eq!(test_gpo.contains_link_location("Domain Admins"), -1);
     }
 
-    // #[test]
-    // fn test_link_status(){
-    //     let mut test_gpo = gen_empty_gpo();
-    //     test_gpo.links.push(
-    //         Link {
-    //             location: "Domain Controllers".to_string(),
-    //             enforced: "No".to_string(),
-    //             status: "Enabled".to_string(),
-    //             path: "labs.local/Domain Controllers".to_string(),
-    //         }
-    //     );
+    #[test]
+    fn test_link_status(){
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.links.push(
+            Link {
+                location: "Domain Controllers".to_string(),
+                enforced: false,
+                enabled: true,
+                path: "labs.local/Domain Controllers".to_string(),
+                link_order: Some(1),
+                inheritance_blocked: false,
+            }
+        );
 
-    //     assert_eq!(test_gpo.is_link_active("Domain Controllers"), true);
-    //     assert_ne!(test_gpo.is_link_active("Domain Admins"), true);
-    // }
+        assert!(test_gpo.contains_link_state("Domain Controllers", "enabled"));
+        assert!(!test_gpo.contains_link_state("Domain Admins", "enabled"));
+        test_gpo.links[0].enabled = false;
+        assert!(!test_gpo.contains_link_state("Domain Controllers", "enabled"));
+    }
 
     #[test]
     fn test_filtering(){
         let mut test_gpo = gen_empty_gpo();
         test_gpo.filtering.push(
-            "NT AUTHORITY\\Authenticated Users".to_string()
+            Principal::parse("NT AUTHORITY\\Authenticated Users")
         );
 
         // Test Equals
-        assert_eq!(test_gpo.contains_filter("NT AUTHORITY\\Authenticated Users"), true);
+        assert!(test_gpo.contains_filter("NT AUTHORITY\\Authenticated Users"));
         // Test Ends With
-        assert_eq!(test_gpo.contains_filter(">Authenticated Users"), true);
+        assert!(test_gpo.contains_filter(">Authenticated Users"));
         // Test Starts With
-        assert_eq!(test_gpo.contains_filter("<NT AUTHORITY"), true);
+        assert!(test_gpo.contains_filter("<NT AUTHORITY"));
     }
 
     #[test]
@@ -1127,17 +1764,18 @@ mod tests {
         test_gpo.delegation.push(
             Delegation {
                 name: "NT AUTHORITY\\Authenticated Users".to_string(),
+                principal: Principal::parse("NT AUTHORITY\\Authenticated Users"),
                 permissions: vec!("Read (from Security Filtering)".to_string()),
                 inherited: "No".to_string()
             }
         );
 
         // Test Equals
-        assert_eq!(test_gpo.contains_delegation_permission("NT AUTHORITY\\Authenticated Users", "Read (from Security Filtering)"), true);
+        assert!(test_gpo.contains_delegation_permission("NT AUTHORITY\\Authenticated Users", "Read (from Security Filtering)"));
         // Test Ends With
-        assert_eq!(test_gpo.contains_delegation_permission(">Authenticated Users", "Read (from Security Filtering)"), true);
+        assert!(test_gpo.contains_delegation_permission(">Authenticated Users", "Read (from Security Filtering)"));
         // Test Starts With
-        assert_eq!(test_gpo.contains_delegation_permission("<NT AUTHORITY", "Read (from Security Filtering)"), true);
+        assert!(test_gpo.contains_delegation_permission("<NT AUTHORITY", "Read (from Security Filtering)"));
     }
 
     #[test]
@@ -1146,12 +1784,13 @@ mod tests {
         test_gpo.policies.push(
             Policy {
                 value: "Debug programs".to_string(),
-                setting: vec!("BUILTIN\\Administrators".to_string())
+                setting: "BUILTIN\\Administrators".to_string(),
+                setting_value: SettingValue::parse("BUILTIN\\Administrators")
             }
         );
 
-        assert_eq!(test_gpo.contains_policy_setting("Debug programs", "BUILTIN\\Administrators"), true);
-        assert_ne!(test_gpo.contains_policy_setting("Debug programs", "Everyone"), true);
+        assert!(test_gpo.contains_policy_setting("Debug programs", "BUILTIN\\Administrators"));
+        assert!(!test_gpo.contains_policy_setting("Debug programs", "Everyone"));
     }
 
     #[test]
@@ -1160,14 +1799,15 @@ mod tests {
         test_gpo.policies.push(
             Policy {
                 value: "Minimum password length".to_string(),
-                setting: vec!("7".to_string())
+                setting: "7".to_string(),
+                setting_value: SettingValue::parse("7")
             }
         );
 
-        assert_eq!(test_gpo.contains_policy_setting("Minimum password length", "#<=14"), true);
-        assert_ne!(test_gpo.contains_policy_setting("Minimum password length", "#>=14"), true);
-        assert_eq!(test_gpo.contains_policy_setting("Minimum password length", "#<8"), true);
-        assert_ne!(test_gpo.contains_policy_setting("Minimum password length", "#>7"), true);
+        assert!(test_gpo.contains_policy_setting("Minimum password length", "#<=14"));
+        assert!(!test_gpo.contains_policy_setting("Minimum password length", "#>=14"));
+        assert!(test_gpo.contains_policy_setting("Minimum password length", "#<8"));
+        assert!(!test_gpo.contains_policy_setting("Minimum password length", "#>7"));
     }
 
     #[test]
@@ -1175,8 +1815,8 @@ mod tests {
         let mut test_gpo = gen_empty_gpo();
         test_gpo.name = "TEST GPO".to_string();
 
-        assert_eq!(test_gpo.query_gpo("Name::Test GPO"), true);
-        assert_ne!(test_gpo.query_gpo("Name::GPO Test"), true);
+        assert!(test_gpo.query_gpo("Name::Test GPO", &now()));
+        assert!(!test_gpo.query_gpo("Name::GPO Test", &now()));
     }
 
     #[test]
@@ -1184,13 +1824,13 @@ mod tests {
         let mut test_gpo = gen_empty_gpo();
         test_gpo.details.set_owner("LABS\\Domain Admins".to_string());
 
-        assert_eq!(test_gpo.query_gpo("Details::LABS\\Domain Admins"), true);
-        assert_eq!(test_gpo.query_gpo("Details::>Domain Admins"), true);
-        assert_eq!(test_gpo.query_gpo("Details::<LABS"), true);
-        assert_ne!(test_gpo.query_gpo("Details::Everyone"), true);
+        assert!(test_gpo.query_gpo("Details::LABS\\Domain Admins", &now()));
+        assert!(test_gpo.query_gpo("Details::>Domain Admins", &now()));
+        assert!(test_gpo.query_gpo("Details::<LABS", &now()));
+        assert!(!test_gpo.query_gpo("Details::Everyone", &now()));
         // Test Is Not
         test_gpo.details.set_owner("Everybody".to_string());
-        assert_eq!(test_gpo.query_gpo("Details::!>Domain Admins"), true)
+        assert!(test_gpo.query_gpo("Details::!>Domain Admins", &now()))
     }
 
     #[test]
@@ -1199,31 +1839,301 @@ mod tests {
         test_gpo.links.push(
             Link {
                 location: "Domain Controllers".to_string(),
-                enforced: "No".to_string(),
-                status: "Enabled".to_string(),
+                enforced: false,
+                enabled: true,
                 path: "labs.local/Domain Controllers".to_string(),
+                link_order: Some(1),
+                inheritance_blocked: false,
             }
         );
 
-        assert_eq!(test_gpo.query_gpo("Links::Domain Controllers"), true);
-        assert_ne!(test_gpo.query_gpo("Links::Everyone"), true);
+        assert!(test_gpo.query_gpo("Links::Domain Controllers", &now()));
+        assert!(!test_gpo.query_gpo("Links::Everyone", &now()));
+        // Test link state
+        assert!(test_gpo.query_gpo("Links::Domain Controllers::enabled", &now()));
+        assert!(test_gpo.query_gpo("Links::Domain Controllers::!enforced", &now()));
+        assert!(!test_gpo.query_gpo("Links::Domain Controllers::enforced", &now()));
+        assert!(test_gpo.query_gpo("Links::<Domain::", &now()));
+        test_gpo.links[0].enforced = true;
+        test_gpo.links[0].enabled = false;
+        assert!(test_gpo.query_gpo("Links::Domain Controllers::enforced", &now()));
+        assert!(!test_gpo.query_gpo("Links::Domain Controllers::enabled", &now()));
+        assert!(test_gpo.get_matching_conditions("Links::Domain Controllers::disabled", &now()).contains("L:Domain Controllers:S:Disabled:Enforced"));
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let path = std::env::temp_dir().join("gpo_insight_test_parse_links.txt");
+        fs::write(&path, "\
+Default Domain Controllers Policy
+Links
+────────────────────
+Location │ Enforced │ Link Status │ Path
+────────────────────
+Domain Controllers │ Yes │ Disabled │ labs.local/Domain Controllers
+
+").unwrap();
+        let test_gpo = GroupPolicy::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(test_gpo.links.len(), 1);
+        assert!(test_gpo.links[0].enforced);
+        assert!(!test_gpo.links[0].enabled);
+        assert_eq!(test_gpo.links[0].path, "labs.local/Domain Controllers");
+        assert_eq!(test_gpo.links[0].link_order, None);
+    }
+
+    #[test]
+    fn test_parse_wrapped_policies() {
+        let path = std::env::temp_dir().join("gpo_insight_test_parse_wrapped_policies.txt");
+        fs::write(&path, "\
+Kerberos Hardening
+Local Policies/Security Options
+──────────────────────────┬──────────────────────────────────
+Policy                    │Setting                           
+──────────────────────────┼──────────────────────────────────
+Network security:         │AES128_HMAC_SHA1, AES256_HMAC_SHA1,
+Configure encryption types│Future encryption types           
+allowed for Kerberos      │                                  
+──────────────────────────┼──────────────────────────────────
+Minimum password length   │14 characters                     
+──────────────────────────┴──────────────────────────────────
+
+").unwrap();
+        let test_gpo = GroupPolicy::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(test_gpo.policies.len(), 2);
+        assert_eq!(test_gpo.policies[0].value, "Network security: Configure encryption types allowed for Kerberos");
+        assert_eq!(test_gpo.policies[0].setting, "AES128_HMAC_SHA1, AES256_HMAC_SHA1, Future encryption types");
+        assert_eq!(test_gpo.policies[0].setting_value.items(), ["AES128_HMAC_SHA1", "AES256_HMAC_SHA1", "Future encryption types"]);
+        assert!(test_gpo.query_gpo("Policy::Network security: Configure encryption types allowed for Kerberos::Future encryption types", &now()));
+    }
+
+    #[test]
+    fn test_struct_to_json() {
+        let output_path = std::env::temp_dir().join("gpo_insight_test_struct_to_json");
+        if output_path.exists() {
+            fs::remove_dir_all(&output_path).unwrap();
+        }
+        fs::create_dir_all(&output_path).unwrap();
+        let gen_test_gpo = || {
+            let mut test_gpo = gen_empty_gpo();
+            test_gpo.name = "Servers/Hardening".to_string();
+            test_gpo.policies.push(
+                Policy {
+                    value: "Minimum password length".to_string(),
+                    setting: "14 characters".to_string(),
+                    setting_value: SettingValue::parse("14 characters")
+                }
+            );
+            test_gpo
+        };
+        let mut id_gpo = gen_empty_gpo();
+        id_gpo.details.id = "{AF0A9F3D-0143-4721-9C82-570172CF71C3}".to_string();
+        let gpos = [gen_test_gpo(), gen_empty_gpo(), gen_test_gpo(), id_gpo, gen_empty_gpo()];
+        struct_to_json(&gpos, &output_path).unwrap();
+        // A second run overwrites the files of the first.
+        struct_to_json(&gpos, &output_path).unwrap();
+
+        let all = fs::read_to_string(output_path.join("gpos.json")).unwrap();
+        let single = fs::read_to_string(output_path.join("json").join("Servers_Hardening.json")).unwrap();
+        let files = fs::read_dir(output_path.join("json")).unwrap().count();
+        assert!(!output_path.join("json").join(".json").exists());
+        assert!(output_path.join("json").join("Servers_Hardening(1).json").exists());
+        assert!(output_path.join("json").join("{AF0A9F3D-0143-4721-9C82-570172CF71C3}.json").exists());
+        assert!(output_path.join("json").join("unnamed.json").exists());
+        assert!(output_path.join("json").join("unnamed(1).json").exists());
+        fs::remove_dir_all(&output_path).unwrap();
+
+        assert_eq!(files, 5);
+
+        assert!(all.starts_with('['));
+        assert!(single.contains("\"name\": \"Servers/Hardening\""));
+        assert!(single.contains("\"Number\": [\n"));
+    }
+
+    #[test]
+    fn test_parse_details() {
+        let path = std::env::temp_dir().join("gpo_insight_test_parse_details.txt");
+        fs::write(&path, "\
+Workstation Hardening
+Details
+────────────────────
+Domain │ labs.local
+Owner │ LABS\\Domain Admins
+Created │ 8/23/2023 3:28:14 PM
+Modified │ 1/5/2024 9:02:11 AM
+User Revisions │ 0 (AD), 0 (SYSVOL)
+Computer Revisions │ 12 (AD), 11 (SYSVOL)
+Unique ID │ {AF0A9F3D-0143-4721-9C82-570172CF71C3}
+GPO Status │ User configuration settings disabled
+
+").unwrap();
+        let test_gpo = GroupPolicy::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(test_gpo.details.status, Some(GpoStatus::UserSettingsDisabled));
+        assert_eq!(test_gpo.details.created.unwrap().to_string(), "2023-08-23 15:28:14");
+        assert_eq!(test_gpo.details.modified.unwrap().to_string(), "2024-01-05 09:02:11");
+        assert_eq!(test_gpo.details.user_revision, Some(Revision { ad: 0, sysvol: 0 }));
+        assert_eq!(test_gpo.details.computer_revision, Some(Revision { ad: 12, sysvol: 11 }));
+        assert_eq!(Revision::from_value("AD (3), SYSVOL (3)"), Some(Revision { ad: 3, sysvol: 3 }));
+        assert_eq!(GpoStatus::from_value("Enabled"), Some(GpoStatus::AllSettingsEnabled));
+        assert!(test_gpo.to_string().contains("Revision Mismatch: Computer AD 12 / SYSVOL 11"));
+    }
+
+    #[test]
+    fn test_parse_coverage() {
+        let path = std::env::temp_dir().join("gpo_insight_test_parse_coverage.txt");
+        fs::write(&path, "\
+Workstation Hardening
+Details
+────────────────────
+Domain │ labs.local
+Owner │ LABS\\Domain Admins
+
+Account Policies/Password Policy
+Policy │ Setting
+Minimum password length │ 14 characters
+
+Extra Registry Settings
+Display Name │ Setting
+Software\\Policies\\Vendor\\Agent │ 1
+
+").unwrap();
+        let test_gpo = GroupPolicy::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(test_gpo.coverage.parsed(), 4);
+        assert_eq!(test_gpo.coverage.unparsed(), 1);
+        let section = test_gpo.coverage.sections.iter().find(|section| !section.unparsed.is_empty()).unwrap();
+        assert_eq!(section.heading, "Extra Registry Settings");
+        assert_eq!(section.skipped, 1);
+        assert!(!test_gpo.is_empty());
+        assert!(gen_empty_gpo().is_empty());
+    }
+
+    #[test]
+    fn test_list_operator_query() {
+        let mut test_gpo = gen_empty_gpo();
+        let mut policy = Policy::new();
+        policy.set_value("Debug programs");
+        policy.set_setting("Local Policies/User Rights Assignment", "BUILTIN\\Administrators, LABS\\Domain Admins, BUILTIN\\Users");
+        test_gpo.policies.push(policy);
+        let mut delegation = Delegation::new();
+        delegation.set_name("NT AUTHORITY\\Authenticated Users");
+        delegation.add_permission("Read (from Security Filtering)");
+        delegation.add_permission("Edit settings");
+        test_gpo.delegation.push(delegation);
+
+        assert!(!test_gpo.query_gpo("Policy::Debug programs::only[Administrators, >Domain Admins]", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::!only[Administrators, >Domain Admins]", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::only[Administrators, >Domain Admins, *S-1-5-32-545]", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::!none of [Users, Everyone]", &now()));
+        assert_eq!(test_gpo.get_matching_conditions("Policy::Debug programs::!only[Administrators, >Domain Admins]", &now()), "Policy: Debug programs | Settings: BUILTIN\\Administrators, LABS\\Domain Admins, BUILTIN\\Users | Offending: BUILTIN\\Users");
+
+        assert!(test_gpo.query_gpo("Delegation::>Authenticated Users::!subset[<Read]::", &now()));
+        assert!(test_gpo.query_gpo("Delegation::>Authenticated Users::exactly[<Read, Edit settings]::", &now()));
+        assert!(test_gpo.get_matching_conditions("Delegation::>Authenticated Users::!subset[<Read]::", &now()).ends_with("Offending: Edit settings"));
+    }
+
+    #[test]
+    fn test_match_modes() {
+        assert!(matches_value("Domain Admins", "Domain admins"));
+        assert!(!matches_value("Domain Admins", "=Domain admins"));
+        assert!(matches_value("Windows Update", "*date"));
+        assert!(!matches_value("Windows Update", "=*DATE"));
+        assert!(matches_value("C:\\Windows\\System32\\cmd.exe", "glob[c:\\windows\\*.exe]"));
+        assert!(!matches_value("C:\\Windows\\System32\\cmd.exe", "=glob[c:\\windows\\*.exe]"));
+        assert!(matches_value("Wired (802.3)", "glob[Wired (802.?)]"));
+        assert!(!matches_value("Wired (802.3)", "glob[Wired]"));
+        assert!(matches_value("%OSDRIVE%\\*", "%OSDRIVE%\\*"));
+        assert!(!Matcher::parse("*S-1-5-32-544").has_modifier());
+        assert!(Matcher::parse("=Administrators").has_modifier());
+        // A literal leading modifier is escaped with "\\". Other backslashes, i.e. of UNC paths, are kept.
+        assert!(matches_value("*foo", "\\*foo"));
+        assert!(!matches_value("barfoo", "\\*foo"));
+        assert!(matches_value("=foo", "\\=foo"));
+        assert!(!matches_value("*FOO", "=\\*foo"));
+        assert!(matches_value("\\\\server\\share", "\\\\server\\share"));
+        assert!(Matcher::parse("\\*foo").has_modifier());
+
+        let mut test_gpo = gen_empty_gpo();
+        let mut delegation = Delegation::new();
+        delegation.set_name("NT AUTHORITY\\Authenticated Users");
+        delegation.add_permission("Read (from Security Filtering)");
+        test_gpo.delegation.push(delegation);
+        assert!(test_gpo.query_gpo("Delegation::*Authenticated::Security Filtering::", &now()));
+        assert!(test_gpo.query_gpo("Delegation::*Authenticated::*Security Filtering::", &now()));
+        assert!(!test_gpo.query_gpo("Delegation::*Authenticated::=security filtering::", &now()));
+        assert!(!test_gpo.query_gpo("Delegation::Authenticated::Security Filtering::", &now()));
+    }
+
+    #[test]
+    fn test_pattern_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.name = "Default Domain Policy".to_string();
+        let mut policy = Policy::new();
+        policy.set_value("Set the intranet update service for detecting updates:");
+        policy.set_setting("Windows Components/Windows Update", "https://wsus.labs.local:8531");
+        test_gpo.policies.push(policy);
+        test_gpo.filtering.push(Principal::parse("S-1-5-21-1004336348-1177238915-682003330-512"));
+
+        assert!(test_gpo.query_gpo("Policy::<Set the intranet update service::~/^https?:\\/\\/[^:\\/]+:\\d+$/", &now()));
+        assert!(!test_gpo.query_gpo("Policy::<Set the intranet update service::~/^HTTP:/", &now()));
+        assert!(test_gpo.query_gpo("Policy::~/^set the intranet/i::!~/:8530$/", &now()));
+        assert!(test_gpo.query_gpo("Filtering::~/^S-1-5-21-.*-5\\d\\d$/", &now()));
+        assert!(!test_gpo.query_gpo("Filtering::~/^S-1-5-21-.*-5\\d\\d\\d$/", &now()));
+        assert!(test_gpo.query_gpo("Name::~/^default/i", &now()));
     }
 
     #[test]
     fn test_filter_query() {
         let mut test_gpo = gen_empty_gpo();
         test_gpo.filtering.push(
-            "NT AUTHORITY\\Authenticated Users".to_string()
+            Principal::parse("NT AUTHORITY\\Authenticated Users")
         );
 
         // Test Equals
-        assert_eq!(test_gpo.query_gpo("Filtering::NT AUTHORITY\\Authenticated Users"), true);
+        assert!(test_gpo.query_gpo("Filtering::NT AUTHORITY\\Authenticated Users", &now()));
         // Test Ends With
-        assert_eq!(test_gpo.query_gpo("Filtering::>Authenticated Users"), true);
+        assert!(test_gpo.query_gpo("Filtering::>Authenticated Users", &now()));
         // Test Starts With
-        assert_eq!(test_gpo.query_gpo("Filtering::<NT AUTHORITY"), true);
+        assert!(test_gpo.query_gpo("Filtering::<NT AUTHORITY", &now()));
+
+        assert!(!test_gpo.query_gpo("Filtering::Everyone", &now()))
+    }
 
-        assert_ne!(test_gpo.query_gpo("Filtering::Everyone"), true)
+    #[test]
+    fn test_applies_query() {
+        let mut test_gpo = gen_empty_gpo();
+        // Filtered to a group that is not allowed to read the GPO
+        test_gpo.filtering.push(Principal::parse("LABS\\Workstations"));
+        let mut delegation = Delegation::new();
+        delegation.set_name("LABS\\Domain Admins");
+        delegation.add_permission("Edit settings");
+        delegation.add_permission("delete");
+        delegation.add_permission("modify security");
+        test_gpo.delegation.push(delegation);
+
+        assert!(!test_gpo.query_gpo("Applies::LABS\\Workstations", &now()));
+        assert!(!test_gpo.query_gpo("Applies::Domain Admins", &now()));
+        assert!(test_gpo.query_gpo("Applies::!Authenticated Users", &now()));
+
+        let mut delegation = Delegation::new();
+        delegation.set_name("*S-1-5-11");
+        delegation.add_permission("Read (from Security Filtering)");
+        test_gpo.delegation.push(delegation);
+        assert!(test_gpo.query_gpo("Applies::NT AUTHORITY\\Authenticated Users", &now()));
+        assert!(test_gpo.query_gpo("Applies::>Authenticated Users", &now()));
+        assert!(test_gpo.get_matching_conditions("Applies::Authenticated Users", &now()).contains("Applies To: NT AUTHORITY\\Authenticated Users | Read: Yes | Apply: Yes"));
+
+        let mut delegation = Delegation::new();
+        delegation.set_name("LABS\\Workstations");
+        delegation.add_permission("Read");
+        test_gpo.delegation.push(delegation);
+        assert!(test_gpo.query_gpo("Applies::LABS\\Workstations", &now()));
     }
 
     #[test]
@@ -1232,30 +2142,31 @@ mod tests {
         test_gpo.delegation.push(
             Delegation {
                 name: "NT AUTHORITY\\Authenticated Users".to_string(),
+                principal: Principal::parse("NT AUTHORITY\\Authenticated Users"),
                 permissions: vec!("Read (from Security Filtering)".to_string()),
                 inherited: "Yes".to_string()
             }
         );
 
         // Test Equals
-        assert_eq!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::"), true);
+        assert!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::", &now()));
         // Test Ends With
-        assert_eq!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::>(from Security Filtering)::"), true);
-        assert_eq!(test_gpo.query_gpo("Delegation::>Authenticated Users::Read (from Security Filtering)::"), true);
+        assert!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::>(from Security Filtering)::", &now()));
+        assert!(test_gpo.query_gpo("Delegation::>Authenticated Users::Read (from Security Filtering)::", &now()));
         // Test Starts With
-        assert_eq!(test_gpo.query_gpo("Delegation::<NT AUTHORITY::Read (from Security Filtering)::"), true);
-        assert_eq!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::<Read::"), true);
+        assert!(test_gpo.query_gpo("Delegation::<NT AUTHORITY::Read (from Security Filtering)::", &now()));
+        assert!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::<Read::", &now()));
         // Test inherited
-        assert_eq!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::Yes"), true);
+        assert!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::Yes", &now()));
         test_gpo.delegation[0].set_inheritence("No");
-        assert_eq!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::No"), true);
+        assert!(test_gpo.query_gpo("Delegation::NT AUTHORITY\\Authenticated Users::Read (from Security Filtering)::No", &now()));
         // Test missing name
-        assert_ne!(test_gpo.query_gpo("Delegation::::Read (from Security Filtering)::"), true);
+        assert!(!test_gpo.query_gpo("Delegation::::Read (from Security Filtering)::", &now()));
         // Test missing permission
-        assert_ne!(test_gpo.query_gpo("Delegation::::NT AUTHORITY\\Authenticated Users::"), true);
+        assert!(!test_gpo.query_gpo("Delegation::::NT AUTHORITY\\Authenticated Users::", &now()));
 
-        assert_ne!(test_gpo.query_gpo("Delegation::Everybody::Read (from Security Filtering)::"), true);
-        assert_ne!(test_gpo.query_gpo("Delegation::Everybody::::"), true);
+        assert!(!test_gpo.query_gpo("Delegation::Everybody::Read (from Security Filtering)::", &now()));
+        assert!(!test_gpo.query_gpo("Delegation::Everybody::::", &now()));
     }
 
     #[test]
@@ -1264,26 +2175,60 @@ mod tests {
         test_gpo.policies.push(
             Policy {
                 value: "Debug programs".to_string(),
-                setting: vec!("BUILTIN\\Administrators".to_string())
+                setting: "BUILTIN\\Administrators".to_string(),
+                setting_value: SettingValue::parse("BUILTIN\\Administrators")
             }
         );
 
         // Test Equals
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::BUILTIN\\Administrators"), true);
+        assert!(test_gpo.query_gpo("Policy::Debug programs::BUILTIN\\Administrators", &now()));
         // Test Ends With
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::>Administrators"), true);
-        assert_eq!(test_gpo.query_gpo("Policy::>programs::BUILTIN\\Administrators"), true);
+        assert!(test_gpo.query_gpo("Policy::Debug programs::>Administrators", &now()));
+        assert!(test_gpo.query_gpo("Policy::>programs::BUILTIN\\Administrators", &now()));
         // Test Starts With
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::<BUILTIN"), true);
-        assert_eq!(test_gpo.query_gpo("Policy::<Debug::BUILTIN\\Administrators"), true);
+        assert!(test_gpo.query_gpo("Policy::Debug programs::<BUILTIN", &now()));
+        assert!(test_gpo.query_gpo("Policy::<Debug::BUILTIN\\Administrators", &now()));
         // Validate the setting can be empty -- Searching for policy's existence
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::"), true);
+        assert!(test_gpo.query_gpo("Policy::Debug programs::", &now()));
         // Validate the policy's value can't be empty
-        assert_ne!(test_gpo.query_gpo("Policy::::BUILTIN\\Administrators"), true);
+        assert!(!test_gpo.query_gpo("Policy::::BUILTIN\\Administrators", &now()));
         // Test Is Not Query
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::!Everyone"), true);
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::!>Authenticated Users"), true);
-        assert_eq!(test_gpo.query_gpo("Policy::Debug programs::!>Administrators"), false);
+        assert!(test_gpo.query_gpo("Policy::Debug programs::!Everyone", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::!>Authenticated Users", &now()));
+        assert!(!test_gpo.query_gpo("Policy::Debug programs::!>Administrators", &now()));
+    }
+
+    #[test]
+    fn test_policy_raw_setting_query() {
+        let mut test_gpo = gen_empty_gpo();
+        let mut lan_manager = Policy::new();
+        lan_manager.set_value("Network security: LAN Manager authentication level");
+        lan_manager.set_setting("Local Policies/Security Options", "Send NTLMv2 response only. Refuse LM & NTLM");
+        test_gpo.policies.push(lan_manager);
+        let mut debug = Policy::new();
+        debug.set_value("Debug programs");
+        debug.set_setting("Local Policies/User Rights Assignment", "BUILTIN\\Administrators, LABS\\Helpdesk");
+        test_gpo.policies.push(debug);
+        let mut banner = Policy::new();
+        banner.set_value("Interactive logon: Message text for users attempting to log on");
+        banner.set_setting("Local Policies/Security Options", "Authorized use only, all activity is logged");
+        test_gpo.policies.push(banner);
+
+        assert!(test_gpo.query_gpo("Policy::Network security: LAN Manager authentication level::Send NTLMv2 response only. Refuse LM & NTLM", &now()));
+        assert!(!test_gpo.query_gpo("Policy::Network security: LAN Manager authentication level::!Send NTLMv2 response only. Refuse LM & NTLM", &now()));
+        // Lists match the raw text or any member
+        assert!(test_gpo.query_gpo("Policy::Debug programs::>Helpdesk", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::BUILTIN\\Administrators, LABS\\Helpdesk", &now()));
+        // Commas in text are not split
+        assert!(test_gpo.query_gpo("Policy::<Interactive logon: Message text::Authorized use only, all activity is logged", &now()));
+        assert!(!test_gpo.query_gpo("Policy::<Interactive logon: Message text::Authorized use only", &now()));
+        // Principals match in any of their forms
+        test_gpo.policies[1].set_setting("Local Policies/User Rights Assignment", "*S-1-5-32-544, LABS\\Helpdesk");
+        assert!(test_gpo.query_gpo("Policy::Debug programs::BUILTIN\\Administrators", &now()));
+        assert!(test_gpo.query_gpo("Policy::Debug programs::>Administrators", &now()));
+        test_gpo.filtering.push(Principal::parse("*S-1-5-11"));
+        assert!(test_gpo.query_gpo("Filtering::NT AUTHORITY\\Authenticated Users", &now()));
+        assert!(test_gpo.query_gpo("Filtering::Authenticated Users", &now()));
     }
 
     #[test]
@@ -1292,12 +2237,28 @@ mod tests {
         test_gpo.policies.push(
             Policy {
                 value: "Minimum password length".to_string(),
-                setting: vec!("7".to_string())
+                setting: "7".to_string(),
+                setting_value: SettingValue::parse("7")
             }
         );
 
-        assert_eq!(test_gpo.query_gpo("Policy::Minimum password length::#>=14"), false);
-        assert_eq!(test_gpo.query_gpo("Policy::Minimum password length::#<14"), true);
+        assert!(!test_gpo.query_gpo("Policy::Minimum password length::#>=14", &now()));
+        assert!(test_gpo.query_gpo("Policy::Minimum password length::#<14", &now()));
+
+        // Cells with units as printed by the report
+        test_gpo.policies.push(
+            Policy {
+                value: "Maximum password age".to_string(),
+                setting: "42 days".to_string(),
+                setting_value: SettingValue::parse("42 days")
+            }
+        );
+        test_gpo.policies[0].setting_value = SettingValue::parse("14 characters");
+        assert!(test_gpo.query_gpo("Policy::Minimum password length::#>=14", &now()));
+        assert!(!test_gpo.query_gpo("Policy::Minimum password length::#<14", &now()));
+        assert!(test_gpo.query_gpo("Policy::Maximum password age::#>30 days", &now()));
+        assert!(test_gpo.query_gpo("Policy::Maximum password age::#<=6 weeks", &now()));
+        assert!(!test_gpo.query_gpo("Policy::Maximum password age::#>365", &now()));
     }
 
     #[test]
@@ -1306,14 +2267,83 @@ mod tests {
         test_gpo.policies.push(
             Policy {
                 value: "Minimum password length".to_string(),
-                setting: vec!("7".to_string())
+                setting: "7".to_string(),
+                setting_value: SettingValue::parse("7")
             }
         );
 
-        assert_eq!(test_gpo.query_gpo("Policy::Minimum password length::#>=14 // This is a comment"), false);
-        assert_eq!(test_gpo.query_gpo(" // This is a comment"), false);
-        assert_eq!(test_gpo.query_gpo("This is a comment"), false);
-        assert_eq!(test_gpo.query_gpo("Policy::Minimum password length::#<14  // This is a comment"), true);
+        assert!(!test_gpo.query_gpo("Policy::Minimum password length::#>=14 // This is a comment", &now()));
+        assert!(!test_gpo.query_gpo(" // This is a comment", &now()));
+        assert!(!test_gpo.query_gpo("This is a comment", &now()));
+        assert!(test_gpo.query_gpo("Policy::Minimum password length::#<14  // This is a comment", &now()));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_applocker_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.application_control = ApplicationControl::parse("Executable Rules\nAction │ User │ Name │ Rule Type\nAllow │ Everyone │ %OSDRIVE%\\* │ Path\n");
+
+        assert!(test_gpo.query_gpo("AppLocker::Exe::Allow::::%OSDRIVE%\\*", &now()));
+        assert!(test_gpo.query_gpo("AppLocker::::Allow::Everyone::<%OSDRIVE%", &now()));
+        assert!(!test_gpo.query_gpo("AppLocker::Script::Allow::::%OSDRIVE%\\*", &now()));
+        assert!(!test_gpo.query_gpo("AppLocker::::Deny::::", &now()));
+        assert!(test_gpo.get_matching_conditions("AppLocker::::Allow::::", &now()).contains("%OSDRIVE%"));
+    }
+
+    #[test]
+    fn test_software_restriction_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.application_control = ApplicationControl::parse("Path Rules\nPath │ Security Level\n%TEMP%\\* │ Unrestricted\n");
+
+        assert!(test_gpo.query_gpo("SoftwareRestriction::<%TEMP%::Unrestricted", &now()));
+        assert!(!test_gpo.query_gpo("SoftwareRestriction::<%TEMP%::Disallowed", &now()));
+    }
+
+    #[test]
+    fn test_firewall_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.firewall = Firewall::parse("Windows Firewall with Advanced Security\nPublic Profile Settings\nFirewall state │ Off\n\nInbound Rules\nName │ Action │ Remote Address\nAny RDP │ Allow │ Any\n");
+
+        assert!(test_gpo.query_gpo("Firewall::Public::Firewall state::Off", &now()));
+        assert!(test_gpo.query_gpo("Firewall::::Firewall state::!<On", &now()));
+        assert!(!test_gpo.query_gpo("Firewall::Domain::Firewall state::", &now()));
+        assert!(test_gpo.query_gpo("FirewallRule::Inbound::Allow::Remote Address::Any", &now()));
+        assert!(!test_gpo.query_gpo("FirewallRule::Outbound::::::", &now()));
+        assert!(test_gpo.get_matching_conditions("FirewallRule::::Allow::::", &now()).contains("Any RDP"));
+    }
+
+    #[test]
+    fn test_certificate_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.public_key_policies = PublicKeyPolicies::parse("Public Key Policies\nTrusted Root Certification Authorities\nIssued To │ Issued By │ Expiration Date\nContoso Root CA │ Contoso Root CA │ 1/1/2001\n");
+
+        assert!(test_gpo.query_gpo("Certificate::Trusted Root Certification Authorities::Subject::Contoso Root CA", &now()));
+        assert!(test_gpo.query_gpo("Certificate::Root::Status::Expired", &now()));
+        assert!(!test_gpo.query_gpo("Certificate::Enterprise Trust::::", &now()));
+        assert!(test_gpo.get_matching_conditions("Certificate::::Issuer::>Root CA", &now()).contains("Contoso Root CA"));
+    }
+
+    #[test]
+    fn test_preference_query() {
+        let mut test_gpo = gen_empty_gpo();
+        test_gpo.preferences = Preferences::parse("Preferences\nLocal Users and Groups\nAdministrators (built-in) (Order: 1)\nAction: Update\nMembers\nAdd member: LABS\\Domain Admins\nAdd member: LABS\\Helpdesk\n");
+
+        assert!(test_gpo.query_gpo("Preference::Local Users and Groups::Action::Update", &now()));
+        assert!(test_gpo.query_gpo("Preference::::Add member::>Helpdesk", &now()));
+        assert!(!test_gpo.query_gpo("Preference::Drive Maps::::", &now()));
+        assert!(test_gpo.get_matching_conditions("Preference::::Name::<Administrators", &now()).contains("Administrators (built-in)"));
+    }
+
+    #[test]
+    fn test_wmi_query() {
+        let mut test_gpo = gen_empty_gpo();
+        assert!(!test_gpo.query_gpo("WMI::Name::", &now()));
+
+        test_gpo.wmi_filter = WmiFilter::parse("WMI Filter Name │ Workstations\nQuery │ root\\CIMv2; SELECT * FROM Win32_OperatingSystem WHERE ProductType = \"1\"\n");
+        assert!(test_gpo.query_gpo("WMI::Name::Workstations", &now()));
+        assert!(test_gpo.query_gpo("WMI::Targeting::workstations", &now()));
+        assert!(!test_gpo.query_gpo("WMI::Targeting::<servers", &now()));
+        assert!(test_gpo.to_string().contains("WMI Filter: Workstations | Targets: workstations"));
+    }
+
+}
@@ -0,0 +1,58 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! GPO Insight as a library: split and parse GPO report exports into GroupPolicy values,
+//! read GPO Query Syntax files and analyze the GPOs against them.
+//!
+//! ```
+//! # use std::fs;
+//! use chrono::Local;
+//! use gpo_insight::{analysis, gpo, query};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! # let dir = std::env::temp_dir().join("gpo_insight_doctest");
+//! # let _ = fs::remove_dir_all(&dir);
+//! # fs::create_dir_all(dir.join("queries"))?;
+//! # let report = "<html xmlns:gpo=\"x\">\n<title>Servers</title>\n<body><table><tr><td>Servers</td></tr></table></body>\n</html>\n";
+//! # fs::write(dir.join("report.html"), report.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect::<Vec<u8>>())?;
+//! # fs::write(dir.join("queries").join("names.queries"), "W -- Name::Servers\n")?;
+//! let (input_path, output_path) = (dir.join("report.html"), dir.join("output"));
+//! let count = gpo::breakdown_gpo(&input_path, &output_path)?;
+//! gpo::gpo_to_text(&output_path)?;
+//! let (gpos, failures) = gpo::text_to_struct(&output_path)?;
+//! let queries = query::parse_dir(&dir.join("queries"))?;
+//! let analysis = analysis::analyze(&gpos, &queries.queries, &Local::now().naive_local());
+//! assert_eq!((count, gpos.len(), failures.len()), (1, 1, 0));
+//! assert_eq!(analysis.findings.len(), 1);
+//! # fs::remove_dir_all(&dir)?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod gpo;
+pub mod query;
+pub mod analysis;
+pub mod coverage;
+pub mod applocker;
+pub mod firewall;
+pub mod pki;
+pub mod preferences;
+pub mod wmi;
+pub mod setting;
+pub mod principal;
+pub mod rule;
+pub mod lint;
+mod table;
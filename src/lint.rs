@@ -0,0 +1,355 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Checks of a query pack that go beyond syntax errors.
+//
+// Every syntax error of the pack is an error, as are queries that can never match, such as
+// "Name::A | !Name::A" or "Policy::X::#<8 | Policy::X::#>14", and rule ids used by more than one
+// rule file. Queries that repeat another query and comments that look like queries are warnings.
+
+use core::cmp::Ordering;
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::Serialize;
+use crate::gpo::has_modifier;
+use crate::query::{self, Definitions, Expr, Field, Query, Severity, Span, DEFINITIONS_FILE};
+use crate::rule::is_rule_file;
+use crate::setting::{is_comparison, SettingValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub source: PathBuf,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(level: Level, source: &Path, span: Span, message: String) -> Self {
+        Self {
+            level,
+            source: source.to_path_buf(),
+            span,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}: {}", self.source.display(), self.span.line, self.span.column, self.level, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Lint {
+    // The number of queries that were read, rule conditions included.
+    pub queries: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Lint {
+    pub fn errors(&self) -> usize {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.level == Level::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.level == Level::Warning).count()
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in self.diagnostics.iter() {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        write!(f, "Queries: {} | Errors: {} | Warnings: {}", self.queries, self.errors(), self.warnings())
+    }
+}
+
+// Conditions and expressions compared by their field and values, so "Name::A" and "name:: A"
+// are the same. Values keep their case, since "=" makes a value case sensitive.
+fn key(expr: &Expr) -> String {
+    match expr {
+        Expr::Condition(condition) => {
+            let values: Vec<&str> = condition.values.iter().map(|value| value.trim()).collect();
+            format!("{:?}::{}", condition.field, values.join("::"))
+        }
+        Expr::Not(expr) => format!("!({})", key(expr)),
+        Expr::And(exprs) => format!("({})", exprs.iter().map(key).collect::<Vec<String>>().join(" | ")),
+        Expr::Or(exprs) => format!("({})", exprs.iter().map(key).collect::<Vec<String>>().join(" || ")),
+    }
+}
+
+// Nested "|" groups are one list of expressions that all have to match.
+fn conjuncts<'a>(exprs: &'a [Expr], all: &mut Vec<&'a Expr>) {
+    for expr in exprs.iter() {
+        match expr {
+            Expr::And(nested) => conjuncts(nested, all),
+            expr => all.push(expr),
+        }
+    }
+}
+
+// A numeric modifier of a Policy condition as a bound, i.e. "#>=14" is a lower bound of 14 that
+// 14 itself satisfies. None unless the policy is matched exactly.
+fn bound(expr: &Expr) -> Option<(String, bool, bool, SettingValue)> {
+    let Expr::Condition(condition) = expr else {
+        return None;
+    };
+    if condition.field != Field::Policy || has_modifier(&condition.values[0]) || !is_comparison(&condition.values[1]) {
+        return None;
+    }
+    let setting = condition.values[1].trim();
+    let (lower, inclusive, operand) = [("#>=", true, true), ("#>", true, false), ("#<=", false, true), ("#<", false, false)].iter()
+        .find_map(|(modifier, lower, inclusive)| setting.strip_prefix(modifier).map(|operand| (*lower, *inclusive, operand)))?;
+    Some((condition.values[0].trim().to_lowercase(), lower, inclusive, SettingValue::parse(operand)))
+}
+
+// Why the conditions of an "|" group can never all match, if they can't.
+fn contradiction(exprs: &[Expr]) -> Option<String> {
+    let mut all: Vec<&Expr> = Vec::new();
+    conjuncts(exprs, &mut all);
+    for (i, expr) in all.iter().enumerate() {
+        for other in all[i + 1..].iter() {
+            let negates = |expr: &Expr, other: &Expr| matches!(expr, Expr::Not(negated) if key(negated) == key(other));
+            if negates(expr, other) || negates(other, expr) {
+                return Some(format!("\"{}\" and \"{}\" can't both match.", expr, other));
+            }
+            let (Some((policy, lower, inclusive, value)), Some((other_policy, other_lower, other_inclusive, other_value))) = (bound(expr), bound(other)) else {
+                continue;
+            };
+            if policy != other_policy || lower == other_lower {
+                continue;
+            }
+            let ordering = if lower { value.compare(&other_value) } else { other_value.compare(&value) };
+            let empty = match ordering {
+                Some(Ordering::Greater) => true,
+                Some(Ordering::Equal) => !(inclusive && other_inclusive),
+                _ => false,
+            };
+            if empty {
+                return Some(format!("No setting is both \"{}\" and \"{}\".", expr, other));
+            }
+        }
+    }
+    None
+}
+
+// A negated contradiction always matches, so only "|" groups and "||" groups of them count.
+fn unreachable(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Condition(_) | Expr::Not(_) => None,
+        Expr::And(exprs) => contradiction(exprs).or_else(|| exprs.iter().find_map(unreachable)),
+        Expr::Or(exprs) => exprs.iter().map(unreachable).collect::<Option<Vec<String>>>().map(|reasons| reasons.join(" ")),
+    }
+}
+
+fn location(query: &Query) -> String {
+    format!("{}:{}:{}", query.source.display(), query.span.line, query.span.column)
+}
+
+// Queries that can never match, queries that repeat an earlier one and rule ids shared by files.
+pub fn check_queries(queries: &[Query], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: HashMap<(Severity, String), &Query> = HashMap::new();
+    let mut ids: HashMap<String, &Query> = HashMap::new();
+    for query in queries.iter() {
+        if let Some(reason) = unreachable(&query.expr) {
+            let message = match query.severity {
+                Severity::Missing => format!("This query can never match, so it is always reported missing. {}", reason),
+                _ => format!("This query can never match. {}", reason),
+            };
+            diagnostics.push(Diagnostic::new(Level::Error, &query.source, query.span, message));
+        }
+        if let Some(rule) = &query.rule {
+            let first = *ids.entry(rule.id.to_lowercase()).or_insert(query);
+            if first.source != query.source {
+                diagnostics.push(Diagnostic::new(Level::Error, &query.source, query.span, format!("The rule id \"{}\" is also used by {}.", rule.id, location(first))));
+            }
+        }
+        match seen.get(&(query.severity, key(&query.expr))) {
+            Some(first) => diagnostics.push(Diagnostic::new(Level::Warning, &query.source, query.span, format!("Duplicate of the query at {}.", location(first)))),
+            None => {
+                seen.insert((query.severity, key(&query.expr)), query);
+            }
+        }
+    }
+}
+
+// "// U -- Policy::..." is a comment, so the query is never run.
+fn is_commented_query(line: &str) -> bool {
+    let Some(comment) = line.trim_start().strip_prefix("//") else {
+        return false;
+    };
+    let Some((flag, expr)) = comment.split_once("--") else {
+        return false;
+    };
+    Severity::from_flag(flag.trim()).is_some() && expr.contains("::")
+}
+
+pub fn check_comments(source: &Path, content: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for (i, line) in content.lines().enumerate() {
+        if is_commented_query(line) {
+            let column = line.chars().take_while(|c| c.is_whitespace()).count() + 1;
+            diagnostics.push(Diagnostic::new(Level::Warning, source, Span { line: i + 1, column }, "This line looks like a query but is a comment, so it is never run. Remove the \"//\" to run it.".to_string()));
+        }
+    }
+}
+
+fn query_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for entry in fs::read_dir(path)? {
+            paths.push(entry?.path());
+        }
+        paths.sort();
+        for path in paths.iter() {
+            query_files(path, files)?;
+        }
+    } else if path.is_file() && query::is_query_file(path) && !is_rule_file(path) {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+// Lint a queries directory like parse_dir reads it, or a single file with the definitions file
+// next to it.
+pub fn lint(path: &Path) -> Result<Lint> {
+    let set = if path.is_dir() {
+        query::parse_dir(path)?
+    } else {
+        let definitions_path = path.parent().unwrap_or(Path::new("")).join(DEFINITIONS_FILE);
+        let definitions = if definitions_path.is_file() && path.file_name() != definitions_path.file_name() {
+            query::parse_file(&definitions_path, &Definitions::default())?.definitions
+        } else {
+            Definitions::default()
+        };
+        query::parse_file(path, &definitions)?
+    };
+    let mut lint = Lint { queries: set.queries.len(), diagnostics: Vec::new() };
+    for e in set.errors.iter() {
+        lint.diagnostics.push(Diagnostic::new(Level::Error, &e.source, e.span, e.message.clone()));
+    }
+    check_queries(&set.queries, &mut lint.diagnostics);
+
+    // Included files outside the directory are checked as well.
+    let mut files: Vec<PathBuf> = Vec::new();
+    query_files(path, &mut files)?;
+    files.extend(set.queries.iter().map(|query| query.source.clone()).chain(set.errors.iter().map(|e| e.source.clone())).filter(|source| !is_rule_file(source)));
+    let mut checked: HashSet<PathBuf> = HashSet::new();
+    for file in files.iter() {
+        // Unreadable files are already reported by the parser.
+        if checked.insert(fs::canonicalize(file).unwrap_or_else(|_| file.clone())) {
+            if let Ok(content) = fs::read_to_string(file) {
+                check_comments(file, &content, &mut lint.diagnostics);
+            }
+        }
+    }
+    lint.diagnostics.sort_by(|a, b| (&a.source, a.span.line, a.span.column).cmp(&(&b.source, b.span.line, b.span.column)));
+    Ok(lint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(content: &str) -> Vec<String> {
+        let set = query::parse_str(Path::new("pack.queries"), content, &Definitions::default());
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        check_queries(&set.queries, &mut diagnostics);
+        check_comments(Path::new("pack.queries"), content, &mut diagnostics);
+        diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(check("U -- Name::A | (Links::Servers || !name:: A)\n"), Vec::<String>::new());
+        assert_eq!(check("U -- Links::Servers | (Name::A | !Name::A)\n"), vec!("pack.queries:1:1: error: This query can never match. \"Name::A\" and \"!Name::A\" can't both match."));
+        assert_eq!(check("W -- Name::B\nM -- Name::A | !name:: A\n"), vec!("pack.queries:2:1: error: This query can never match, so it is always reported missing. \"Name::A\" and \"!name:: A\" can't both match."));
+        assert_eq!(check("M -- Policy::Minimum password length::#<8 | Policy::minimum password length::#>=14\n")[0], "pack.queries:1:1: error: This query can never match, so it is always reported missing. No setting is both \"Policy::Minimum password length::#<8\" and \"Policy::minimum password length::#>=14\".");
+        assert_eq!(check("U -- Policy::Lockout duration::#<30 minutes | Policy::Lockout duration::#>1 hour\n").len(), 1);
+        assert_eq!(check("U -- Policy::Minimum password length::#<14 | Policy::Minimum password length::#>=8\n").len(), 0);
+        assert_eq!(check("U -- Policy::Minimum password length::#<=14 | Policy::Minimum password length::#>=14\n").len(), 0);
+        assert_eq!(check("U -- Policy::Minimum password length::#<14 | Policy::Minimum password length::#>=14\n").len(), 1);
+        assert_eq!(check("U -- Policy::*password::#<8 | Policy::*password::#>14\n").len(), 0);
+        assert_eq!(check("U -- !(Name::A | !Name::A) || (Name::B | !Name::B)\n").len(), 0);
+        assert_eq!(check("U -- (Name::A | !Name::A) || (Name::B | !Name::B)\n").len(), 1);
+    }
+
+    #[test]
+    fn test_duplicates_and_comments() {
+        let diagnostics = check("\
+U -- Name::Default Domain Policy
+W -- Name::Default Domain Policy
+U -- name::Default Domain Policy // again
+// U -- Policy::Debug programs::>Users
+// It is best to configure the GPO to 14 -- see the PSOs
+");
+        assert_eq!(diagnostics, vec!(
+            "pack.queries:3:1: warning: Duplicate of the query at pack.queries:1:1.",
+            "pack.queries:4:1: warning: This line looks like a query but is a comment, so it is never run. Remove the \"//\" to run it.",
+        ));
+    }
+
+    #[test]
+    fn test_rule_ids() {
+        let rule = "[[rule]]\nid = \"KRB-001\"\ntitle = \"DES\"\nseverity = \"U\"\nconditions = [\"Name::A\"]\n";
+        let mut queries = crate::rule::parse_str(Path::new("a.toml"), rule, &Definitions::default(), "").queries;
+        queries.extend(crate::rule::parse_str(Path::new("b.toml"), &rule.replace("Name::A", "Name::B"), &Definitions::default(), "").queries);
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        check_queries(&queries, &mut diagnostics);
+        assert_eq!(diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<String>>(), vec!("b.toml:5:16: error: The rule id \"KRB-001\" is also used by a.toml:5:16."));
+    }
+
+    #[test]
+    fn test_lint_shipped_queries() {
+        let lint = lint(&Path::new(env!("CARGO_MANIFEST_DIR")).join("queries")).unwrap();
+        assert_eq!(lint.diagnostics, Vec::<Diagnostic>::new());
+        assert!(lint.queries > 0);
+    }
+
+    #[test]
+    fn test_lint_other_files() {
+        let root = std::env::temp_dir().join(format!("gpo-insight-lint-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("names.queries"), "W -- Name::Servers\n").unwrap();
+        fs::write(root.join("README.md"), "// W -- Name::Workstations\n").unwrap();
+        fs::write(root.join(".DS_Store"), [0x00, 0xff, 0xfe]).unwrap();
+
+        let lint = lint(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(lint.diagnostics, Vec::<Diagnostic>::new());
+        assert_eq!(lint.queries, 1);
+    }
+}
@@ -17,29 +17,63 @@
 use std::
     path::{Path, PathBuf}
 ;
-use clap::{arg, Parser};
+use clap::{Parser, Subcommand};
 use anyhow::{anyhow, Result};
+use chrono::Local;
+use gpo_insight::{analysis, coverage, gpo, lint, query};
 mod cli;
-mod gpo;
-mod analysis;
 
 #[derive(Parser)]
-#[command(name="GPO Insight", version)]
+#[command(name="GPO Insight", version, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    // Only optional for subcommands. Clap requires it otherwise.
     #[arg(short='i', long, required = true)]
-    input: String,
+    input: Option<String>,
     #[arg(short='o', long, default_value = "PWD")]
     output: String,
+    // Global so it can be given before or after a subcommand, i.e. "-q pack lint".
+    #[arg(short='q', long, global = true)]
+    queries: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check a queries directory or file without analysing any GPOs.
+    Lint,
+}
+
+fn queries_path(queries: &Option<String>) -> Result<PathBuf> {
+    match queries {
+        Some(queries) => cli::parse_input_path(queries),
+        None => analysis::queries_dir(),
+    }
+}
+
+// Print every diagnostic. Any error fails the command, so a query pack can be checked in CI.
+fn run_lint(queries: &Option<String>) -> Result<()> {
+    let lint = lint::lint(&queries_path(queries)?)?;
+    println!("{}", lint);
+    if lint.errors() > 0 {
+        return Err(anyhow!("The queries have {} error(s).", lint.errors()));
+    }
+    Ok(())
 }
 
 fn main() -> Result<()>{
     println!("GPO Insight v{}, Copyright (C) 2024 Kiersten Gross\n\nThis project is licensed under the GNU General Public License v3.0. <https://www.gnu.org/licenses/>.\nThis program comes with ABSOLUTELY NO WARRANTY.\n", env!("CARGO_PKG_VERSION").to_owned());
     let args = Args::parse();
-    let input_path:PathBuf = cli::parse_input_path(&args.input)?;
+    if let Some(Command::Lint) = &args.command {
+        return run_lint(&args.queries);
+    }
+    let input_path:PathBuf = cli::parse_input_path(args.input.as_deref().ok_or(anyhow!("An input path is required."))?)?;
     let output_path = match cli::parse_output_path(&args.output) {
         Ok(v) => {
-            gpo::breakdown_gpo(&input_path, &v)?;
-            gpo::gpo_to_text(&v)?;
+            let count = gpo::breakdown_gpo(&input_path, &v)?;
+            println!("GPO Breakdown successful. {} GPOs detected.", count);
+            let count = gpo::gpo_to_text(&v)?;
+            println!("{} GPOs successfully converted from HTML to TXT.", count);
             v
         } Err(e) => {
             if e.to_string().starts_with("The output directory ") && e.to_string().ends_with(" already exists.") {
@@ -50,12 +84,27 @@ fn main() -> Result<()>{
             }
         }
     };
-    let policies = gpo::text_to_struct(&output_path)?;
+    let (policies, failures) = gpo::text_to_struct(&output_path)?;
+    for (path, e) in failures.iter() {
+        println!("Could not parse GPO {}.\n{}", path.display(), e);
+    }
+    gpo::struct_to_json(&policies, &output_path)?;
+    println!("{} GPOs successfully written to JSON.", policies.len());
+    let overall = coverage::write_report(&policies, &output_path)?;
+    println!("{:.1}% of the report rows were parsed. See coverage.txt for the rows that were not.", overall);
     let analysis_path = output_path.join("analysis");
     if !analysis_path.exists() {
         std::fs::create_dir_all(&analysis_path)?;
     }
-    analysis::analyze(&policies, &analysis_path)?;
+    let queries = query::parse_dir(&queries_path(&args.queries)?)?;
+    for e in queries.errors.iter() {
+        println!("{}", e);
+    }
+    let analysis = analysis::analyze(&policies, &queries.queries, &Local::now().naive_local());
+    analysis::write_reports(&analysis, &analysis_path)?;
+    for report in analysis.reports() {
+        println!("{}", report);
+    }
 
     Ok(())
 }
\ No newline at end of file
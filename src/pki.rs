@@ -0,0 +1,290 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Public Key Policies: certificates deployed to the machine stores and auto-enrollment.
+//
+// Each store is a heading ("Trusted Root Certification Authorities", "Enterprise Trust", ...)
+// followed by an "Issued To │ Issued By │ Expiration Date │ Intended Purposes" table.
+// Certificates under "Encrypting File System" are EFS data recovery agents.
+// Auto-enrollment is a "Certificate Services Client - Auto-Enrollment" heading with "Setting │ Value" rows.
+
+use core::fmt;
+use std::collections::HashSet;
+use serde::Serialize;
+use chrono::NaiveDateTime;
+use crate::gpo::matches_value;
+use crate::table;
+
+const CERTIFICATE_STORES: &[&str] = &[
+    "trusted root certification authorities",
+    "enterprise trust",
+    "intermediate certification authorities",
+    "trusted publishers",
+    "untrusted certificates",
+    "trusted people",
+    "third-party root certification authorities",
+    "encrypting file system",
+    "data recovery agents",
+];
+
+// SHA-1 thumbprints and names of the Microsoft root certificates shipped with Windows.
+const MICROSOFT_ROOTS: &[(&str, &str)] = &[
+    ("A43489159A520F0D93D032CCAF37E7FE20A8B419", "Microsoft Root Authority"),
+    ("CDD4EEAE6000AC7F40C3802C171E30148030C072", "Microsoft Root Certificate Authority"),
+    ("3B1EFD3A66EA28B16697394703A72CA340A05BD5", "Microsoft Root Certificate Authority 2010"),
+    ("8F43288AD272F3103B6FB1428485EA3014C0BCFE", "Microsoft Root Certificate Authority 2011"),
+    ("73A5E64A3BFF8316FF0EDCCC618A906E4EAE4D74", "Microsoft RSA Root Certificate Authority 2017"),
+    ("999A64C37FF47D9FAB95F14769891460EEC4C3C5", "Microsoft ECC Root Certificate Authority 2017"),
+    ("7F88CD7223F3C813818C994614A89C99FA3B5247", "Microsoft Authenticode(tm) Root Authority"),
+    ("245C97DF7514E7CF2DF8BE72AE957B9E04741E85", "Copyright (c) 1997 Microsoft Corp."),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Certificate {
+    pub store: String,
+    pub subject: String,
+    pub issuer: String,
+    pub thumbprint: String,
+    pub expires: Option<NaiveDateTime>,
+    pub expiration: String,
+    pub purposes: String,
+}
+
+impl Certificate {
+    fn new(store: &str) -> Self {
+        Self {
+            store: store.to_string(),
+            subject: String::new(),
+            issuer: String::new(),
+            thumbprint: String::new(),
+            expires: None,
+            expiration: String::new(),
+            purposes: String::new(),
+        }
+    }
+    pub fn is_root(&self) -> bool {
+        let store = self.store.to_lowercase();
+        store.contains("root certification authorities")
+    }
+    pub fn is_efs_recovery_agent(&self) -> bool {
+        let store = self.store.to_lowercase();
+        store == "encrypting file system" || store == "data recovery agents"
+    }
+    // Names can be forged, so only the thumbprint identifies a Microsoft root.
+    pub fn is_microsoft(&self) -> bool {
+        let thumbprint: String = self.thumbprint.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+        MICROSOFT_ROOTS.iter().any(|(root, _)| root.eq_ignore_ascii_case(&thumbprint))
+    }
+    // GPMC reports usually leave the thumbprint out. Such a certificate can only be matched
+    // by name, which doesn't prove it is the real Microsoft root.
+    pub fn has_microsoft_name(&self) -> bool {
+        MICROSOFT_ROOTS.iter().any(|(_, name)| name.eq_ignore_ascii_case(self.subject.trim()) && name.eq_ignore_ascii_case(self.issuer.trim()))
+    }
+    pub fn is_expired(&self, now: &NaiveDateTime) -> bool {
+        match self.expires {
+            Some(expires) => expires < *now,
+            None => false,
+        }
+    }
+    fn status(&self, now: &NaiveDateTime) -> &'static str {
+        if self.is_expired(now) { "Expired" } else { "Valid" }
+    }
+    fn field(&self, name: &str, now: &NaiveDateTime) -> Option<String> {
+        match name.to_lowercase().as_str() {
+            "subject" | "issued to" => Some(self.subject.clone()),
+            "issuer" | "issued by" => Some(self.issuer.clone()),
+            "thumbprint" => Some(self.thumbprint.clone()),
+            "expires" | "expiration date" => Some(self.expiration.clone()),
+            "purposes" | "intended purposes" => Some(self.purposes.clone()),
+            "status" => Some(self.status(now).to_string()),
+            _ => None,
+        }
+    }
+    // "Root" is shorthand for both root certification authority stores.
+    fn is_store(&self, store: &str) -> bool {
+        store.is_empty() || matches_value(&self.store, store) || (store.eq_ignore_ascii_case("root") && self.is_root())
+    }
+    // Blank values match anything.
+    pub fn is_match(&self, store: &str, field: &str, value: &str, now: &NaiveDateTime) -> bool {
+        if !self.is_store(store) {
+            return false;
+        }
+        if field.is_empty() {
+            return value.is_empty();
+        }
+        match self.field(field, now) {
+            Some(current) => value.is_empty() || matches_value(&current, value),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Certificate: {} | Subject: {} | Issuer: {} | Expires: {}", self.store, self.subject, self.issuer, self.expiration)?;
+        if !self.thumbprint.is_empty() {
+            write!(f, " | Thumbprint: {}", self.thumbprint)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicKeyPolicies {
+    pub certificates: Vec<Certificate>,
+    pub auto_enrollment: Vec<(String, String)>,
+}
+
+impl Default for PublicKeyPolicies {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PublicKeyPolicies {
+    pub fn new() -> Self {
+        Self {
+            certificates: Vec::new(),
+            auto_enrollment: Vec::new(),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        Self::parse_lines(content, &mut HashSet::new())
+    }
+
+    // Like parse, adding the line numbers of the rows it read to "parsed".
+    pub fn parse_lines(content: &str, parsed: &mut HashSet<usize>) -> Self {
+        let mut pki = Self::new();
+        let mut pki_block = false;
+        let mut auto_enrollment = false;
+        let mut store = String::new();
+        let mut header: Vec<String> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || table::is_border(line) {
+                continue;
+            }
+            if table::is_section_heading(line) {
+                pki_block = line.trim().eq_ignore_ascii_case("public key policies");
+                store = String::new();
+                auto_enrollment = false;
+                continue;
+            }
+            if !pki_block {
+                continue;
+            }
+            if !table::is_row(line) {
+                let heading = line.trim();
+                if CERTIFICATE_STORES.contains(&heading.to_lowercase().as_str()) {
+                    store = heading.to_string();
+                    auto_enrollment = false;
+                    header = Vec::new();
+                } else if heading.to_lowercase().contains("auto-enrollment") {
+                    auto_enrollment = true;
+                    store = String::new();
+                }
+                continue;
+            }
+
+            let row = table::split_row(line);
+            if auto_enrollment {
+                if row.len() >= 2 && !["policy", "option", "setting"].contains(&row[0].to_lowercase().as_str()) {
+                    pki.auto_enrollment.push((row[0].clone(), row[1].clone()));
+                    parsed.insert(index);
+                }
+            }
+            else if !store.is_empty() {
+                if table::column(&row, &["Issued To", "Subject"]).is_some() {
+                    header = row;
+                    parsed.insert(index);
+                    continue;
+                }
+                // A thumbprint printed beneath the certificate row.
+                if row[0].eq_ignore_ascii_case("thumbprint") {
+                    if let Some(certificate) = pki.certificates.last_mut() {
+                        certificate.thumbprint = table::cell(&row, Some(1));
+                        parsed.insert(index);
+                    }
+                    continue;
+                }
+                if header.is_empty() {
+                    continue;
+                }
+                let mut certificate = Certificate::new(&store);
+                certificate.subject = table::cell(&row, table::column(&header, &["Issued To", "Subject"]));
+                certificate.issuer = table::cell(&row, table::column(&header, &["Issued By", "Issuer"]));
+                certificate.expiration = table::cell(&row, table::column(&header, &["Expiration Date", "Expires", "Valid To"]));
+                certificate.expires = table::parse_date(&certificate.expiration);
+                certificate.purposes = table::cell(&row, table::column(&header, &["Intended Purposes", "Purposes"]));
+                certificate.thumbprint = table::cell(&row, table::column(&header, &["Thumbprint"]));
+                pki.certificates.push(certificate);
+                parsed.insert(index);
+            }
+        }
+
+        pki
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const REPORT: &str = "\
+Public Key Policies
+Certificate Services Client - Auto-Enrollment Settings
+Policy │ Setting
+Automatic certificate management │ Enabled
+
+Trusted Root Certification Authorities
+Issued To │ Issued By │ Expiration Date │ Intended Purposes
+Contoso Root CA │ Contoso Root CA │ 1/1/2030 12:00:00 AM │ <All>
+Thumbprint │ 0123456789ABCDEF
+Fabrikam Issuing CA │ Fabrikam Root │ 1/1/2028 │ <All>
+Microsoft Root Certificate Authority 2011 │ Microsoft Root Certificate Authority 2011 │ 3/22/2036 │ <All>
+Thumbprint │ 8f 43 28 8a d2 72 f3 10 3b 6f b1 42 84 85 ea 30 14 c0 bc fe
+Microsoft Root Authority Update │ Microsoft Root Authority Update │ 1/1/2030 │ <All>
+
+Encrypting File System
+Issued To │ Issued By │ Expiration Date │ Intended Purposes
+Administrator │ Administrator │ 3/4/2019 1:00:00 PM │ File Recovery
+
+Administrative Templates
+Issued To │ Issued By
+Not a certificate │ Nobody
+";
+
+    #[test]
+    fn test_parse_certificates() {
+        let pki = PublicKeyPolicies::parse(REPORT);
+        let now = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(pki.auto_enrollment, vec!(("Automatic certificate management".to_string(), "Enabled".to_string())));
+        assert_eq!(pki.certificates.len(), 5);
+        assert!(pki.certificates[0].is_root());
+        assert!(!pki.certificates[0].is_microsoft());
+        assert_eq!(pki.certificates[0].thumbprint, "0123456789ABCDEF");
+        assert!(!pki.certificates[0].is_expired(&now));
+        assert_eq!(pki.certificates[1].issuer, "Fabrikam Root");
+        assert!(pki.certificates[2].is_microsoft());
+        assert!(!pki.certificates[3].is_microsoft());
+        assert!(pki.certificates[4].is_efs_recovery_agent());
+        assert!(pki.certificates[4].is_expired(&now));
+        assert!(pki.certificates[4].is_match("", "Status", "Expired", &now));
+        assert!(pki.certificates[0].is_match("Root", "Subject", "<Contoso", &now));
+    }
+}
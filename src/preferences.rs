@@ -0,0 +1,353 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Group Policy Preferences.
+//
+// Preferences are not printed as tables. Each extension ("Drive Maps", "Scheduled Tasks", ...) is a
+// heading, each item starts with a "<name> (Order: <n>)" line, and the item's settings follow as
+// "Key: Value" lines grouped under sub-headings such as "General", "Properties" and "Common".
+// Item-level targeting is printed under a "Filters" sub-heading as one line per filter,
+// e.g. "the computer is a member of the security group LABS\Workstations" then "AND ...".
+
+use core::fmt;
+use std::collections::HashSet;
+use serde::Serialize;
+use crate::gpo::matches_value;
+use crate::table;
+
+const CATEGORIES: &[&str] = &[
+    "applications",
+    "data sources",
+    "devices",
+    "drive maps",
+    "environment",
+    "files",
+    "folder options",
+    "folders",
+    "ini files",
+    "internet settings",
+    "local users and groups",
+    "network options",
+    "network shares",
+    "power options",
+    "printers",
+    "regional options",
+    "registry",
+    "scheduled tasks",
+    "services",
+    "shortcuts",
+    "start menu",
+];
+
+const SUB_HEADINGS: &[&str] = &[
+    "general",
+    "properties",
+    "common",
+    "options",
+    "filters",
+    "description",
+    "members",
+    "triggers",
+    "actions",
+    "conditions",
+    "settings",
+    "recovery",
+    "task",
+    "schedule",
+];
+
+// Keys that name the account an item runs as or is configured for.
+const RUN_AS_KEYS: &[&str] = &["run as", "run as user", "runas", "user name", "log on as", "logon as", "account", "service account"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum PreferenceAction {
+    Create,
+    Replace,
+    Update,
+    Delete,
+}
+
+impl PreferenceAction {
+    fn from_value(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "create" | "c" => Some(Self::Create),
+            "replace" | "r" => Some(Self::Replace),
+            "update" | "u" => Some(Self::Update),
+            "delete" | "d" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PreferenceAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self {
+            Self::Create => "Create",
+            Self::Replace => "Replace",
+            Self::Update => "Update",
+            Self::Delete => "Delete",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Preference {
+    pub category: String,
+    pub name: String,
+    pub order: Option<u32>,
+    pub action: Option<PreferenceAction>,
+    pub properties: Vec<(String, String)>,
+    pub run_as: String,
+    pub targeting: String,
+}
+
+impl Preference {
+    fn new(category: &str, name: &str, order: Option<u32>) -> Self {
+        Self {
+            category: category.to_string(),
+            name: name.to_string(),
+            order,
+            action: None,
+            properties: Vec::new(),
+            run_as: String::new(),
+            targeting: String::new(),
+        }
+    }
+    fn add_property(&mut self, key: &str, value: &str) {
+        if key.eq_ignore_ascii_case("action") {
+            self.action = PreferenceAction::from_value(value);
+        }
+        if self.run_as.is_empty() && RUN_AS_KEYS.contains(&key.to_lowercase().as_str()) {
+            self.run_as = value.to_string();
+        }
+        self.properties.push((key.to_string(), value.to_string()));
+    }
+    fn add_targeting(&mut self, filter: &str) {
+        if !self.targeting.is_empty() {
+            self.targeting.push(' ');
+        }
+        self.targeting.push_str(filter);
+    }
+    // "Name", "Action", "Run As" and "Targeting" are always available. Anything else is a property,
+    // which can repeat (i.e. one "Add member" per member).
+    fn field(&self, name: &str) -> Vec<String> {
+        match name.to_lowercase().as_str() {
+            "name" => vec!(self.name.clone()),
+            "action" => self.action.iter().map(|action| action.to_string()).collect(),
+            "run as" => vec!(self.run_as.clone()),
+            "targeting" => vec!(self.targeting.clone()),
+            _ => self.properties.iter().filter(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.clone()).collect(),
+        }
+    }
+    // Blank values match anything.
+    pub fn is_match(&self, category: &str, field: &str, value: &str) -> bool {
+        if !category.is_empty() && !matches_value(&self.category, category) {
+            return false;
+        }
+        if field.is_empty() {
+            return value.is_empty();
+        }
+        self.field(field).iter().any(|current| value.is_empty() || matches_value(current, value))
+    }
+}
+
+impl fmt::Display for Preference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match &self.action {
+            Some(action) => action.to_string(),
+            None => String::new(),
+        };
+        write!(f, "Preference: {} | {}", self.category, self.name)?;
+        if let Some(order) = self.order {
+            write!(f, " (Order: {})", order)?;
+        }
+        write!(f, " | Action: {}", action)?;
+        if !self.run_as.is_empty() {
+            write!(f, " | Run As: {}", self.run_as)?;
+        }
+        if !self.targeting.is_empty() {
+            write!(f, " | Targeting: {}", self.targeting)?;
+        }
+        Ok(())
+    }
+}
+
+// Split "<name> (Order: <n>)" into the item name and order.
+fn order_line(line: &str) -> Option<(&str, Option<u32>)> {
+    let index = line.rfind(" (Order: ")?;
+    let order = line[index + " (Order: ".len()..].strip_suffix(')')?;
+    Some((&line[..index], order.trim().parse::<u32>().ok()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Preferences {
+    pub items: Vec<Preference>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preferences {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        Self::parse_lines(content, &mut HashSet::new())
+    }
+
+    // Like parse, adding the line numbers of the rows it read to "parsed".
+    pub fn parse_lines(content: &str, parsed: &mut HashSet<usize>) -> Self {
+        let mut preferences = Self::new();
+        let mut preferences_block = false;
+        let mut category = String::new();
+        let mut filters_block = false;
+
+        let lines: Vec<(usize, &str)> = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !table::is_border(line)).collect();
+        for (i, &(index, line)) in lines.iter().enumerate() {
+            let trimmed = line.trim();
+            let lowered = trimmed.to_lowercase();
+
+            // "Registry" and "Windows Settings" are both report sections and preference groupings.
+            if preferences_block && CATEGORIES.contains(&lowered.as_str()) {
+                category = trimmed.to_string();
+                filters_block = false;
+                continue;
+            }
+            if table::is_section_heading(line) {
+                if lowered == "preferences" {
+                    preferences_block = true;
+                } else if lowered != "windows settings" && lowered != "control panel settings" {
+                    preferences_block = false;
+                }
+                category = String::new();
+                filters_block = false;
+                continue;
+            }
+            if !preferences_block || category.is_empty() {
+                continue;
+            }
+
+            // New item
+            if let Some((name, order)) = order_line(trimmed) {
+                preferences.items.push(Preference::new(&category, name, order));
+                filters_block = false;
+                continue;
+            }
+            // The item type printed above each item, e.g. "Drive Map (Drive: H)".
+            if lines.get(i + 1).and_then(|(_, next)| order_line(next.trim())).is_some() {
+                continue;
+            }
+            let Some(item) = preferences.items.last_mut() else {
+                continue;
+            };
+            if item.category != category {
+                continue;
+            }
+
+            if SUB_HEADINGS.contains(&lowered.as_str()) {
+                filters_block = lowered == "filters";
+            }
+            else if filters_block {
+                item.add_targeting(trimmed);
+                parsed.insert(index);
+            }
+            else if table::is_row(line) {
+                let row = table::split_row(line);
+                if row.len() >= 2 && !row[0].is_empty() {
+                    item.add_property(&row[0], &row[1]);
+                    parsed.insert(index);
+                }
+            }
+            else if let Some((key, value)) = trimmed.split_once(": ") {
+                item.add_property(key.trim(), value.trim());
+                parsed.insert(index);
+            }
+        }
+
+        preferences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "\
+Preferences
+Windows Settings
+Drive Maps
+Drive Map (Drive: H)
+H: (Order: 1)
+General
+Action: Update
+Properties
+Letter: H
+Location: \\\\fileserver\\home
+Common
+Options
+Run in logged-on user's security context (user policy option): No
+Filters
+the computer is a member of the security group LABS\\Workstations
+AND the user is a member of the security group LABS\\Staff
+Drive Map (Drive: S)
+S: (Order: 2)
+General
+Action: Delete
+
+Control Panel Settings
+Scheduled Tasks
+Cleanup (Order: 1)
+General
+Action │ Replace
+Run as │ NT AUTHORITY\\System
+
+Administrative Templates
+Policy │ Setting
+Not a preference │ Enabled
+";
+
+    #[test]
+    fn test_parse_preferences() {
+        let preferences = Preferences::parse(REPORT);
+        assert_eq!(preferences.items.len(), 3);
+
+        let drive = &preferences.items[0];
+        assert_eq!(drive.category, "Drive Maps");
+        assert_eq!(drive.name, "H:");
+        assert_eq!(drive.order, Some(1));
+        assert_eq!(drive.action, Some(PreferenceAction::Update));
+        assert!(drive.is_match("Drive Maps", "Location", "\\\\fileserver\\home"));
+        assert_eq!(drive.targeting, "the computer is a member of the security group LABS\\Workstations AND the user is a member of the security group LABS\\Staff");
+
+        assert_eq!(preferences.items[1].action, Some(PreferenceAction::Delete));
+        assert!(preferences.items[1].targeting.is_empty());
+
+        let task = &preferences.items[2];
+        assert_eq!(task.category, "Scheduled Tasks");
+        assert_eq!(task.action, Some(PreferenceAction::Replace));
+        assert_eq!(task.run_as, "NT AUTHORITY\\System");
+        assert!(task.is_match("Scheduled Tasks", "Run As", ">System"));
+        assert!(task.is_match("", "Action", "Replace"));
+        assert!(!task.is_match("Drive Maps", "", ""));
+    }
+}
@@ -0,0 +1,223 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Users and groups as printed in delegation, security filtering and user rights assignments.
+//
+// The same principal can be printed as "BUILTIN\Administrators", "Administrators", "*S-1-5-32-544"
+// or a localized name such as "Administratoren". Well-known principals are resolved to a single
+// identity so that any of these forms are treated as equal.
+
+use core::fmt;
+use serde::Serialize;
+use crate::gpo::{has_modifier, matches_value};
+
+struct WellKnown {
+    // The SID, or "-<RID>" for principals relative to a domain.
+    sid: &'static str,
+    domain: &'static str,
+    name: &'static str,
+    aliases: &'static [&'static str],
+}
+
+const WELL_KNOWN: &[WellKnown] = &[
+    WellKnown { sid: "S-1-1-0", domain: "", name: "Everyone", aliases: &["Jeder", "Tout le monde", "Todos"] },
+    WellKnown { sid: "S-1-3-0", domain: "", name: "CREATOR OWNER", aliases: &["Ersteller-Besitzer", "Créateur propriétaire"] },
+    WellKnown { sid: "S-1-5-2", domain: "NT AUTHORITY", name: "NETWORK", aliases: &["Netzwerk", "Réseau"] },
+    WellKnown { sid: "S-1-5-4", domain: "NT AUTHORITY", name: "INTERACTIVE", aliases: &["Interaktiv"] },
+    WellKnown { sid: "S-1-5-6", domain: "NT AUTHORITY", name: "SERVICE", aliases: &["Dienst"] },
+    WellKnown { sid: "S-1-5-7", domain: "NT AUTHORITY", name: "ANONYMOUS LOGON", aliases: &["Anonymous"] },
+    WellKnown { sid: "S-1-5-9", domain: "NT AUTHORITY", name: "ENTERPRISE DOMAIN CONTROLLERS", aliases: &["Domänencontroller der Organisation", "Contrôleurs de domaine d'entreprise"] },
+    WellKnown { sid: "S-1-5-11", domain: "NT AUTHORITY", name: "Authenticated Users", aliases: &["Authentifizierte Benutzer", "Utilisateurs authentifiés", "Usuarios autentificados"] },
+    WellKnown { sid: "S-1-5-18", domain: "NT AUTHORITY", name: "SYSTEM", aliases: &["Local System", "LocalSystem"] },
+    WellKnown { sid: "S-1-5-19", domain: "NT AUTHORITY", name: "LOCAL SERVICE", aliases: &["Lokaler Dienst", "Service local"] },
+    WellKnown { sid: "S-1-5-20", domain: "NT AUTHORITY", name: "NETWORK SERVICE", aliases: &["Netzwerkdienst", "Service réseau"] },
+    WellKnown { sid: "S-1-5-113", domain: "NT AUTHORITY", name: "Local account", aliases: &["Lokales Konto", "Compte local"] },
+    WellKnown { sid: "S-1-5-114", domain: "NT AUTHORITY", name: "Local account and member of Administrators group", aliases: &[] },
+    WellKnown { sid: "S-1-5-32-544", domain: "BUILTIN", name: "Administrators", aliases: &["Administratoren", "Administrateurs", "Administradores"] },
+    WellKnown { sid: "S-1-5-32-545", domain: "BUILTIN", name: "Users", aliases: &["Benutzer", "Utilisateurs", "Usuarios"] },
+    WellKnown { sid: "S-1-5-32-546", domain: "BUILTIN", name: "Guests", aliases: &["Gäste", "Invités", "Invitados"] },
+    WellKnown { sid: "S-1-5-32-547", domain: "BUILTIN", name: "Power Users", aliases: &["Hauptbenutzer", "Utilisateurs avec pouvoir"] },
+    WellKnown { sid: "S-1-5-32-548", domain: "BUILTIN", name: "Account Operators", aliases: &["Konten-Operatoren", "Opérateurs de compte"] },
+    WellKnown { sid: "S-1-5-32-549", domain: "BUILTIN", name: "Server Operators", aliases: &["Server-Operatoren", "Opérateurs de serveur"] },
+    WellKnown { sid: "S-1-5-32-550", domain: "BUILTIN", name: "Print Operators", aliases: &["Druck-Operatoren", "Opérateurs d'impression"] },
+    WellKnown { sid: "S-1-5-32-551", domain: "BUILTIN", name: "Backup Operators", aliases: &["Sicherungs-Operatoren", "Opérateurs de sauvegarde"] },
+    WellKnown { sid: "S-1-5-32-552", domain: "BUILTIN", name: "Replicator", aliases: &["Duplications-Operatoren", "Duplicateurs"] },
+    WellKnown { sid: "S-1-5-32-554", domain: "BUILTIN", name: "Pre-Windows 2000 Compatible Access", aliases: &["Prä-Windows 2000 kompatibler Zugriff", "Accès compatible pré-Windows 2000"] },
+    WellKnown { sid: "S-1-5-32-555", domain: "BUILTIN", name: "Remote Desktop Users", aliases: &["Remotedesktopbenutzer", "Utilisateurs du Bureau à distance"] },
+    WellKnown { sid: "S-1-5-32-559", domain: "BUILTIN", name: "Performance Log Users", aliases: &["Leistungsprotokollbenutzer"] },
+    WellKnown { sid: "S-1-5-32-562", domain: "BUILTIN", name: "Distributed COM Users", aliases: &["Distributed COM-Benutzer"] },
+    WellKnown { sid: "S-1-5-32-568", domain: "BUILTIN", name: "IIS_IUSRS", aliases: &[] },
+    WellKnown { sid: "S-1-5-32-573", domain: "BUILTIN", name: "Event Log Readers", aliases: &["Ereignisprotokollleser"] },
+    WellKnown { sid: "S-1-5-32-580", domain: "BUILTIN", name: "Remote Management Users", aliases: &["Remoteverwaltungsbenutzer"] },
+    WellKnown { sid: "-500", domain: "", name: "Administrator", aliases: &["Administrateur"] },
+    WellKnown { sid: "-501", domain: "", name: "Guest", aliases: &["Gast", "Invité"] },
+    WellKnown { sid: "-502", domain: "", name: "krbtgt", aliases: &[] },
+    WellKnown { sid: "-512", domain: "", name: "Domain Admins", aliases: &["Domänen-Admins", "Admins du domaine", "Admins. del dominio"] },
+    WellKnown { sid: "-513", domain: "", name: "Domain Users", aliases: &["Domänen-Benutzer", "Utilisateurs du domaine", "Usuarios del dominio"] },
+    WellKnown { sid: "-514", domain: "", name: "Domain Guests", aliases: &["Domänen-Gäste", "Invités du domaine"] },
+    WellKnown { sid: "-515", domain: "", name: "Domain Computers", aliases: &["Domänencomputer", "Ordinateurs du domaine", "Equipos del dominio"] },
+    WellKnown { sid: "-516", domain: "", name: "Domain Controllers", aliases: &["Domänencontroller", "Contrôleurs de domaine"] },
+    WellKnown { sid: "-517", domain: "", name: "Cert Publishers", aliases: &["Zertifikatherausgeber", "Éditeurs de certificats"] },
+    WellKnown { sid: "-518", domain: "", name: "Schema Admins", aliases: &["Schema-Admins", "Administrateurs du schéma"] },
+    WellKnown { sid: "-519", domain: "", name: "Enterprise Admins", aliases: &["Organisations-Admins", "Administrateurs de l'entreprise", "Admins. de empresas"] },
+    WellKnown { sid: "-520", domain: "", name: "Group Policy Creator Owners", aliases: &["Richtlinien-Ersteller-Besitzer", "Propriétaires créateurs de la stratégie de groupe"] },
+    WellKnown { sid: "-521", domain: "", name: "Read-only Domain Controllers", aliases: &["Schreibgeschützte Domänencontroller", "Contrôleurs de domaine en lecture seule"] },
+    WellKnown { sid: "-526", domain: "", name: "Key Admins", aliases: &["Schlüsseladministratoren"] },
+    WellKnown { sid: "-527", domain: "", name: "Enterprise Key Admins", aliases: &["Unternehmensschlüsseladministratoren"] },
+];
+
+// Domain prefixes that only qualify a well-known principal and do not change which one it is.
+const BUILTIN_DOMAINS: &[&str] = &["builtin", "nt authority", "vordefiniert", "nt-autorität", "autorite nt", "autorité nt"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Principal {
+    // The text as printed in the report.
+    pub raw: String,
+    pub domain: String,
+    pub name: String,
+    pub sid: Option<String>,
+    // The SID when the principal is well-known, or "-<RID>" for a well-known domain principal
+    // printed by name, whose domain SID isn't in the report.
+    pub identity: Option<String>,
+}
+
+fn well_known_sid(sid: &str) -> Option<&'static WellKnown> {
+    WELL_KNOWN.iter().find(|known| known.sid == sid).or_else(|| {
+        // S-1-5-21-<domain>-<RID>
+        let rid = sid.strip_prefix("S-1-5-21-")?.rsplit('-').next()?;
+        WELL_KNOWN.iter().find(|known| known.sid.strip_prefix('-') == Some(rid))
+    })
+}
+
+fn well_known_name(name: &str) -> Option<&'static WellKnown> {
+    WELL_KNOWN.iter().find(|known| known.name.eq_ignore_ascii_case(name) || known.aliases.iter().any(|alias| alias.to_lowercase() == name.to_lowercase()))
+}
+
+impl Principal {
+    pub fn parse(value: &str) -> Self {
+        let raw = value.trim();
+        let unstarred = raw.trim_start_matches('*');
+        if unstarred.to_uppercase().starts_with("S-1-") {
+            let sid = unstarred.to_uppercase();
+            let known = well_known_sid(&sid);
+            return Self {
+                raw: raw.to_string(),
+                domain: known.map(|known| known.domain.to_string()).unwrap_or_default(),
+                name: known.map(|known| known.name.to_string()).unwrap_or_else(|| sid.clone()),
+                identity: known.map(|_| sid.clone()),
+                sid: Some(sid),
+            };
+        }
+
+        let (domain, name) = match raw.rsplit_once('\\') {
+            Some((domain, name)) => (domain.trim(), name.trim()),
+            None => ("", raw),
+        };
+        let known = well_known_name(name).filter(|known| {
+            // A builtin name qualified by a real domain is a different principal, i.e. LABS\Administrators.
+            known.domain.is_empty() || domain.is_empty() || BUILTIN_DOMAINS.contains(&domain.to_lowercase().as_str())
+        });
+        Self {
+            raw: raw.to_string(),
+            domain: match known {
+                Some(known) if !known.domain.is_empty() => known.domain.to_string(),
+                _ => domain.to_string(),
+            },
+            name: known.map(|known| known.name.to_string()).unwrap_or_else(|| name.to_string()),
+            sid: None,
+            identity: known.map(|known| known.sid.to_string()),
+        }
+    }
+
+    fn is_same_domain(&self, other: &Principal) -> bool {
+        self.domain.is_empty() || other.domain.is_empty() || self.domain.eq_ignore_ascii_case(&other.domain)
+    }
+
+    // Well-known principals are equal when they resolve to the same identity. Domain SIDs only equal
+    // the same SID, while a domain principal printed by name equals any domain SID with its RID.
+    // Others compare by name, and by domain when both sides have one.
+    pub fn is_equivalent(&self, other: &Principal) -> bool {
+        match (&self.identity, &other.identity) {
+            (Some(identity), Some(other_identity)) => match (identity.starts_with('-'), other_identity.starts_with('-')) {
+                (true, true) => identity == other_identity && self.is_same_domain(other),
+                (true, false) => other_identity.starts_with("S-1-5-21-") && other_identity.ends_with(identity.as_str()),
+                (false, true) => identity.starts_with("S-1-5-21-") && identity.ends_with(other_identity.as_str()),
+                (false, false) => identity == other_identity,
+            },
+            (None, None) => self.name.eq_ignore_ascii_case(&other.name) && self.is_same_domain(other),
+            _ => false,
+        }
+    }
+
+    // Modifiers are checked against every form of the principal. Anything else must be an equivalent principal.
+    pub fn matches(&self, value: &str) -> bool {
+        if has_modifier(value) {
+            let mut forms = vec!(self.raw.clone(), self.to_string(), self.name.clone());
+            forms.extend(self.sid.iter().cloned());
+            forms.iter().any(|form| matches_value(form, value))
+        } else {
+            matches_value(&self.raw, value) || self.is_equivalent(&Principal::parse(value))
+        }
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.domain.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}\\{}", self.domain, self.name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_principal() {
+        let principal = Principal::parse("*S-1-5-32-544");
+        assert_eq!(principal.to_string(), "BUILTIN\\Administrators");
+        assert_eq!(principal.sid, Some("S-1-5-32-544".to_string()));
+
+        let principal = Principal::parse("LABS\\jdoe");
+        assert_eq!(principal.domain, "LABS");
+        assert_eq!(principal.name, "jdoe");
+        assert!(principal.identity.is_none());
+
+        assert_eq!(Principal::parse("S-1-5-21-1004336348-1177238915-682003330-512").to_string(), "Domain Admins");
+        assert_eq!(Principal::parse("Authentifizierte Benutzer").to_string(), "NT AUTHORITY\\Authenticated Users");
+    }
+
+    #[test]
+    fn test_equivalent_principals() {
+        let administrators = Principal::parse("BUILTIN\\Administrators");
+        assert!(administrators.matches("Administrators"));
+        assert!(administrators.matches("*S-1-5-32-544"));
+        assert!(administrators.matches("Administratoren"));
+        assert!(!administrators.matches("LABS\\Administrators"));
+        assert!(Principal::parse("*S-1-5-32-544").matches(">Administrators"));
+        assert!(Principal::parse("*S-1-5-11").matches("<NT AUTHORITY"));
+        assert!(Principal::parse("LABS\\Domain Admins").matches("*S-1-5-21-1004336348-1177238915-682003330-512"));
+        assert!(Principal::parse("*S-1-5-21-1004336348-1177238915-682003330-512").matches("Domain Admins"));
+        assert!(Principal::parse("*S-1-5-21-1004336348-1177238915-682003330-512").matches("S-1-5-21-1004336348-1177238915-682003330-512"));
+        assert!(!Principal::parse("*S-1-5-21-1004336348-1177238915-682003330-512").matches("*S-1-5-21-2086372718-945386102-1603529118-512"));
+        assert!(!Principal::parse("LABS\\Domain Admins").matches("CORP\\Domain Admins"));
+        assert!(Principal::parse("LABS\\jdoe").matches("jdoe"));
+        assert!(!Principal::parse("LABS\\jdoe").matches("CORP\\jdoe"));
+    }
+}
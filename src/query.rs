@@ -0,0 +1,974 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// GPO Query Syntax read from the ".queries" files.
+//
+// A query is a flag, "--" and an expression of conditions, i.e.
+// "U -- Policy::Minimum password length::#<14 | Links::>Servers::enabled". Conditions are joined
+// by "|" (and) and "||" (or), negated by a leading "!" and grouped with parentheses. "!" binds
+// tightest and "||" loosest. Each file is parsed once into queries whose conditions know their
+// field, so GPOs are never matched against text.
+// A line is a comment when it starts with "//", and "//" after whitespace ends a query with a
+// comment. "let NAME = [...]" defines a list that later conditions use as "$NAME", and
+// "include PATH" or "import PATH" read another file or directory. Every other line has to be a
+// query, or it is reported as a syntax error. ".toml" files hold rules instead, see rule.rs.
+
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use crate::gpo::{is_link_state, pattern_end, parse_pattern, GroupPolicy};
+use crate::rule::{self, Rule};
+use crate::setting::is_comparison;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Severity {
+    Desirable,
+    Undesirable,
+    Warning,
+    Missing,
+}
+
+impl Severity {
+    pub(crate) fn from_flag(flag: &str) -> Option<Self> {
+        match flag.to_lowercase().as_str() {
+            "d" => Some(Self::Desirable),
+            "u" => Some(Self::Undesirable),
+            "w" => Some(Self::Warning),
+            "m" => Some(Self::Missing),
+            _ => None,
+        }
+    }
+
+    // The severity of a rule file, i.e. "undesirable" or "U".
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "desirable" => Some(Self::Desirable),
+            "undesirable" => Some(Self::Undesirable),
+            "warning" => Some(Self::Warning),
+            "missing" => Some(Self::Missing),
+            _ => Self::from_flag(name),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Desirable => write!(f, "Desirables"),
+            Self::Undesirable => write!(f, "Undesirables"),
+            Self::Warning => write!(f, "Warning"),
+            Self::Missing => write!(f, "Missings"),
+        }
+    }
+}
+
+// The GPO field a condition is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Field {
+    Name,
+    Details,
+    Links,
+    Filtering,
+    Applies,
+    Delegation,
+    Policy,
+    AppLocker,
+    SoftwareRestriction,
+    FirewallRule,
+    Firewall,
+    Certificate,
+    Preference,
+    Wmi,
+}
+
+// Field names, the number of "::" separated segments they take and their syntax.
+const FIELDS: &[(&str, Field, &[usize], &str)] = &[
+    ("name", Field::Name, &[2], "Name::Value"),
+    ("details", Field::Details, &[2, 3], "Details::Owner or Details::Field::Value"),
+    ("links", Field::Links, &[2, 3], "Links::Location or Links::Location::State"),
+    ("filtering", Field::Filtering, &[2], "Filtering::Value"),
+    ("applies", Field::Applies, &[2], "Applies::Principal"),
+    ("delegation", Field::Delegation, &[4], "Delegation::Name::Permissions::Inherited"),
+    ("policy", Field::Policy, &[3], "Policy::Value::Setting"),
+    ("applocker", Field::AppLocker, &[5], "AppLocker::Collection::Action::User::Condition"),
+    ("softwarerestriction", Field::SoftwareRestriction, &[3], "SoftwareRestriction::Rule::SecurityLevel"),
+    ("firewallrule", Field::FirewallRule, &[5], "FirewallRule::Direction::Action::Field::Value"),
+    ("firewall", Field::Firewall, &[4], "Firewall::Profile::Setting::Value"),
+    ("certificate", Field::Certificate, &[4], "Certificate::Store::Field::Value"),
+    ("preference", Field::Preference, &[4], "Preference::Category::Field::Value"),
+    ("wmi", Field::Wmi, &[3], "WMI::Field::Value"),
+];
+
+// A line and column in a query file, both starting at 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxError {
+    pub source: PathBuf,
+    pub span: Span,
+    pub message: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(line: usize, column: usize, message: String) -> Self {
+        Self {
+            source: PathBuf::new(),
+            span: Span { line, column },
+            message,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.source.display(), self.span.line, self.span.column, self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+// The file of a queries directory whose definitions every other queries file can use.
+pub const DEFINITIONS_FILE: &str = "definitions.queries";
+
+const DEFINITION_EXAMPLE: &str = "let TIER0 = [Administrators, >Domain Admins]";
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Lists defined with "let NAME = [...]". A "$NAME" that is a whole value or list item is replaced
+// by the entries of the list separated by ", ", so it is meant for list operators, i.e.
+// "!subset[$TIER0, >Service]".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Definitions {
+    lists: HashMap<String, Vec<String>>,
+}
+
+impl Definitions {
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.lists.get(name).map(|entries| entries.as_slice())
+    }
+
+    pub fn insert(&mut self, name: &str, entries: Vec<String>) {
+        self.lists.insert(name.to_string(), entries);
+    }
+
+    // Add the lists of other, replacing lists of the same name.
+    pub fn merge(&mut self, other: &Definitions) {
+        self.lists.extend(other.lists.iter().map(|(name, entries)| (name.clone(), entries.clone())));
+    }
+
+    // Replace every "$NAME" that is a whole value or list item, so "$" inside a value such as
+    // "\\srv\$share" is kept. "$$" is a literal "$", i.e. "$$Recycle.Bin". Patterns are kept as
+    // written, since "$" is part of their syntax. An unknown name is an error at its byte offset.
+    fn expand(&self, text: &str) -> Result<String, (usize, String)> {
+        let mut expanded = String::new();
+        // Whether only whitespace was read since the start of the value or list item.
+        let mut item_start = true;
+        let mut index = 0;
+        while let Some(c) = text[index..].chars().next() {
+            if let Some(end) = pattern_end(&text[index..]) {
+                expanded.push_str(&text[index..=index + end]);
+                index += end + "/".len();
+                item_start = false;
+                continue;
+            }
+            let rest = &text[index + c.len_utf8()..];
+            if c == '$' && rest.starts_with('$') {
+                expanded.push('$');
+                index += "$$".len();
+                item_start = false;
+                continue;
+            }
+            if c == '$' && item_start && rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                let name = &rest[..rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len())];
+                let after = rest[name.len()..].trim_start();
+                if after.is_empty() || after.starts_with(',') || after.starts_with(']') {
+                    let Some(entries) = self.get(name) else {
+                        return Err((index, format!("Unknown variable \"${}\". Define it first, i.e. \"let {} = [...]\". Write \"$$\" for a \"$\" in a value.", name, name)));
+                    };
+                    expanded.push_str(&entries.join(", "));
+                    index += "$".len() + name.len();
+                    item_start = false;
+                    continue;
+                }
+            }
+            expanded.push(c);
+            index += c.len_utf8();
+            item_start = (item_start && c.is_whitespace()) || c == '[' || c == ',';
+        }
+        Ok(expanded)
+    }
+
+    // "let NAME = [entry, ...]". Entries can use the lists defined before them.
+    fn parse_definition(&mut self, code: &str, line: usize) -> Result<(), SyntaxError> {
+        let error = |offset: usize, message: String| SyntaxError::new(line, column(code, offset), message);
+        let start = code.len() - code.trim_start().len() + "let".len();
+        let name_start = start + (code[start..].len() - code[start..].trim_start().len());
+        let name_end = code[name_start..].find(|c: char| !is_name_char(c)).map(|end| name_start + end).unwrap_or(code.len());
+        let name = &code[name_start..name_end];
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(error(name_start, format!("Expected a name of letters, digits and \"_\" after \"let\", i.e. \"{}\".", DEFINITION_EXAMPLE)));
+        }
+        let rest = code[name_end..].trim_start();
+        let Some(list) = rest.strip_prefix('=') else {
+            return Err(error(code.len() - rest.len(), format!("Expected \"=\" after \"let {}\", i.e. \"{}\".", name, DEFINITION_EXAMPLE)));
+        };
+        let list = list.trim_start();
+        let list_start = code.len() - list.len();
+        let list = list.trim_end();
+        let Some(entries) = list.strip_prefix('[').and_then(|list| list.strip_suffix(']')) else {
+            return Err(error(list_start, format!("Expected a list in brackets, i.e. \"{}\".", DEFINITION_EXAMPLE)));
+        };
+        let mut expanded: Vec<String> = Vec::new();
+        let mut offset = list_start + "[".len();
+        for entry in entries.split(',') {
+            let trimmed = entry.trim();
+            if !trimmed.is_empty() {
+                let entry_start = offset + (entry.len() - entry.trim_start().len());
+                let entry = self.expand(trimmed).map_err(|(index, message)| error(entry_start + index, message))?;
+                // A list used in a list adds its entries.
+                expanded.extend(entry.split(", ").map(|entry| entry.to_string()));
+            }
+            offset += entry.len() + ",".len();
+        }
+        self.insert(name, expanded);
+        Ok(())
+    }
+}
+
+fn is_definition(code: &str) -> bool {
+    code.trim_start().strip_prefix("let").is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+// A single "Field::Value..." condition. "values" are the segments after the field name.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Condition {
+    pub field: Field,
+    pub values: Vec<String>,
+    pub text: String,
+    pub span: Span,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, SyntaxError> {
+        let code = strip_comment(text);
+        let trimmed = code.trim_start();
+        parse_condition(trimmed.trim_end(), 1, column(code, code.len() - trimmed.len()), &Definitions::default())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// The column of a byte offset into a line.
+fn column(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+// Everything before a comment. "//" only starts a comment at the start of the line or after
+// whitespace, so values such as "http://" are kept.
+fn strip_comment(line: &str) -> &str {
+    let mut previous: Option<char> = None;
+    for (index, c) in line.char_indices() {
+        if line[index..].starts_with("//") && previous.map(|p| p.is_whitespace()).unwrap_or(true) {
+            return &line[..index];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+fn parse_condition(text: &str, line: usize, column: usize, definitions: &Definitions) -> Result<Condition, SyntaxError> {
+    if text.is_empty() {
+        return Err(SyntaxError::new(line, column, "Expected a condition, i.e. \"Policy::Value::Setting\".".to_string()));
+    }
+    let segments: Vec<&str> = text.split("::").collect();
+    let name = segments[0].trim().to_lowercase();
+    let Some((_, field, counts, usage)) = FIELDS.iter().find(|(field_name, ..)| *field_name == name) else {
+        let names: Vec<&str> = FIELDS.iter().map(|(_, _, _, usage)| usage.split("::").next().unwrap_or_default()).collect();
+        return Err(SyntaxError::new(line, column, format!("Unknown field \"{}\". Conditions start with one of {}.", segments[0].trim(), names.join(", "))));
+    };
+    if !counts.contains(&segments.len()) {
+        return Err(SyntaxError::new(line, column, format!("Expected {} but found {} \"::\" separated segments.", usage, segments.len())));
+    }
+    let mut values: Vec<String> = Vec::new();
+    let mut offset = 0;
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_column = |start: usize| column + text[..offset + start].chars().count();
+        let value = definitions.expand(segment).map_err(|(start, message)| SyntaxError::new(line, segment_column(start), message))?;
+        let pattern = value.trim_start();
+        let pattern = pattern.strip_prefix('!').unwrap_or(pattern);
+        let pattern = pattern.strip_prefix('=').unwrap_or(pattern);
+        if pattern.starts_with("~/") {
+            if let Err(error) = parse_pattern(pattern.trim_end()) {
+                // Patterns aren't expanded, so the offset is the same in the segment.
+                return Err(SyntaxError::new(line, segment_column(segment.len() - pattern.len()), error.to_string()));
+            }
+        }
+        if pattern.starts_with('#') && !is_comparison(pattern) {
+            return Err(SyntaxError::new(line, segment_column(segment.len() - segment.trim_start().len()), format!("Invalid numeric modifier \"{}\". Expected \"#<\", \"#<=\", \"#>\" or \"#>=\" followed by a number or a date, i.e. \"#<14\".", pattern.trim_end())));
+        }
+        if *field == Field::Links && index == 2 && !is_link_state(&value) {
+            return Err(SyntaxError::new(line, segment_column(segment.len() - segment.trim_start().len()), format!("Unknown link state \"{}\". Expected enforced, enabled, disabled or blocked, each optionally negated with \"!\".", value.trim())));
+        }
+        if index > 0 {
+            values.push(value);
+        }
+        offset += segment.len() + "::".len();
+    }
+    Ok(Condition {
+        field: *field,
+        values,
+        text: text.to_string(),
+        span: Span { line, column },
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Expr {
+    Condition(Condition),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    // "now" is the time relative dates such as "older than 3 years" are measured from.
+    pub fn evaluate(&self, gpo: &GroupPolicy, now: &NaiveDateTime) -> bool {
+        match self {
+            Self::Condition(condition) => gpo.evaluate(condition, now),
+            Self::Not(expr) => !expr.evaluate(gpo, now),
+            Self::And(exprs) => exprs.iter().all(|expr| expr.evaluate(gpo, now)),
+            Self::Or(exprs) => exprs.iter().any(|expr| expr.evaluate(gpo, now)),
+        }
+    }
+
+    // Every condition of the expression, in the order they were written.
+    pub fn conditions(&self) -> Vec<&Condition> {
+        match self {
+            Self::Condition(condition) => vec!(condition),
+            Self::Not(expr) => expr.conditions(),
+            Self::And(exprs) | Self::Or(exprs) => exprs.iter().flat_map(|expr| expr.conditions()).collect(),
+        }
+    }
+
+    // Moves the span of every condition, i.e. from a rule condition into its rule file.
+    pub(crate) fn map_spans(&mut self, map: &impl Fn(Span) -> Span) {
+        match self {
+            Self::Condition(condition) => condition.span = map(condition.span),
+            Self::Not(expr) => expr.map_spans(map),
+            Self::And(exprs) | Self::Or(exprs) => exprs.iter_mut().for_each(|expr| expr.map_spans(map)),
+        }
+    }
+
+    // Satisfied conditions of every satisfied branch. A negation is satisfied by what a GPO
+    // doesn't have, so it has no values to show.
+    fn matching_values(&self, gpo: &GroupPolicy, now: &NaiveDateTime, matching: &mut Vec<String>) {
+        match self {
+            Self::Condition(condition) => {
+                if gpo.evaluate(condition, now) {
+                    let matching_string = gpo.matching_values(condition, now);
+                    if !matching_string.is_empty() && !matching.iter().any(|current| current.contains(&matching_string)) {
+                        matching.push(matching_string);
+                    }
+                }
+            }
+            Self::Not(_) => {}
+            Self::And(exprs) | Self::Or(exprs) => {
+                for expr in exprs.iter().filter(|expr| expr.evaluate(gpo, now)) {
+                    expr.matching_values(gpo, now, matching);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Condition(condition) => write!(f, "{}", condition),
+            Self::Not(expr) => match expr.as_ref() {
+                Self::Condition(_) | Self::Not(_) => write!(f, "!{}", expr),
+                _ => write!(f, "!({})", expr),
+            },
+            Self::And(exprs) => {
+                let exprs: Vec<String> = exprs.iter().map(|expr| match expr {
+                    Self::Or(_) => format!("({})", expr),
+                    _ => expr.to_string(),
+                }).collect();
+                write!(f, "{}", exprs.join(" | "))
+            }
+            Self::Or(exprs) => {
+                let exprs: Vec<String> = exprs.iter().map(|expr| expr.to_string()).collect();
+                write!(f, "{}", exprs.join(" || "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Query {
+    pub severity: Severity,
+    pub expr: Expr,
+    // The query file the query was read from.
+    pub source: PathBuf,
+    pub span: Span,
+    // The subdirectory of the queries directory the file is in, i.e. "domain_controllers/logon".
+    pub category: String,
+    // The rule of a rule file the query checks. Queries of ".queries" files have none.
+    pub rule: Option<Rule>,
+}
+
+impl Query {
+    // Blank lines and comments are Ok(None).
+    pub fn parse(text: &str) -> Result<Option<Self>, SyntaxError> {
+        parse_line(text, 1, &Definitions::default())
+    }
+
+    pub fn matches(&self, gpo: &GroupPolicy, now: &NaiveDateTime) -> bool {
+        self.expr.evaluate(gpo, now)
+    }
+
+    pub fn conditions(&self) -> Vec<&Condition> {
+        self.expr.conditions()
+    }
+
+    // The values of a matching GPO that satisfied the conditions.
+    pub fn matching_values(&self, gpo: &GroupPolicy, now: &NaiveDateTime) -> Vec<String> {
+        let mut matching: Vec<String> = Vec::new();
+        self.expr.matching_values(gpo, now, &mut matching);
+        matching
+    }
+}
+
+// Each "|" separated part of the query on its own line.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = match &self.expr {
+            Expr::And(exprs) => exprs.iter().map(|expr| Expr::And(vec!(expr.clone())).to_string()).collect(),
+            expr => vec!(expr.to_string()),
+        };
+        write!(f, "Query Condition(s):\n\t{}", parts.join("\n\t"))
+    }
+}
+
+// Recursive descent over the expression after "--". Positions are byte offsets into the line.
+struct ExprParser<'a> {
+    code: &'a str,
+    definitions: &'a Definitions,
+    position: usize,
+    line: usize,
+    // Parentheses opened and not yet closed.
+    depth: usize,
+}
+
+impl ExprParser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.code[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, position: usize, message: &str) -> SyntaxError {
+        SyntaxError::new(self.line, column(self.code, position), message.to_string())
+    }
+
+    // expr || expr ...
+    fn parse_or(&mut self) -> Result<Expr, SyntaxError> {
+        let mut exprs = vec!(self.parse_and()?);
+        while self.code[self.position..].starts_with("||") {
+            self.position += "||".len();
+            exprs.push(self.parse_and()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::Or(exprs) })
+    }
+
+    // expr | expr ...
+    fn parse_and(&mut self) -> Result<Expr, SyntaxError> {
+        let mut exprs = vec!(self.parse_unary()?);
+        while self.code[self.position..].starts_with('|') && !self.code[self.position..].starts_with("||") {
+            self.position += "|".len();
+            exprs.push(self.parse_unary()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::And(exprs) })
+    }
+
+    // !expr, (expr) or a condition
+    fn parse_unary(&mut self) -> Result<Expr, SyntaxError> {
+        self.skip_whitespace();
+        let rest = &self.code[self.position..];
+        if rest.starts_with('!') {
+            self.position += "!".len();
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if rest.starts_with('(') {
+            let open = self.position;
+            self.position += "(".len();
+            self.depth += 1;
+            let expr = self.parse_or()?;
+            if !self.code[self.position..].starts_with(')') {
+                return Err(self.error(open, "This \"(\" is never closed."));
+            }
+            self.position += ")".len();
+            self.depth -= 1;
+            self.skip_whitespace();
+            return Ok(expr);
+        }
+        self.parse_condition()
+    }
+
+    // A condition runs until "|" or the ")" closing its group. Parentheses inside the condition,
+    // i.e. "Wired Network (IEEE 802.3) Policies", are part of its values.
+    fn parse_condition(&mut self) -> Result<Expr, SyntaxError> {
+        let start = self.position;
+        let mut nested = 0;
+        let mut end = self.code.len();
+        let mut index = 0;
+        while let Some(c) = self.code[start + index..].chars().next() {
+            // Patterns are kept whole, so "|" and parentheses inside them aren't operators.
+            if let Some(pattern) = pattern_end(&self.code[start + index..]) {
+                index += pattern + "/".len();
+                continue;
+            }
+            match c {
+                '|' => {
+                    end = start + index;
+                    break;
+                }
+                '(' => nested += 1,
+                ')' if nested > 0 => nested -= 1,
+                ')' if self.depth > 0 => {
+                    end = start + index;
+                    break;
+                }
+                _ => {}
+            }
+            index += c.len_utf8();
+        }
+        self.position = end;
+        let text = self.code[start..end].trim_end();
+        Ok(Expr::Condition(parse_condition(text, self.line, column(self.code, start), self.definitions)?))
+    }
+}
+
+fn parse_line(text: &str, line: usize, definitions: &Definitions) -> Result<Option<Query>, SyntaxError> {
+    let code = strip_comment(text);
+    if code.trim().is_empty() {
+        return Ok(None);
+    }
+    let start = code.len() - code.trim_start().len();
+    let Some(separator) = code.find("--") else {
+        return Err(SyntaxError::new(line, column(code, start), "Expected a flag followed by \"--\", i.e. \"U -- Policy::Value::Setting\". Comments start with \"//\".".to_string()));
+    };
+    let flag = code[..separator].trim();
+    let Some(severity) = Severity::from_flag(flag) else {
+        return Err(SyntaxError::new(line, column(code, start), format!("Unknown flag \"{}\". Expected D, U, W or M.", flag)));
+    };
+
+    Ok(Some(Query {
+        severity,
+        expr: parse_expr(code, separator + "--".len(), line, definitions)?,
+        source: PathBuf::new(),
+        span: Span { line, column: column(code, start) },
+        category: String::new(),
+        rule: None,
+    }))
+}
+
+// The expression of "code" from the byte offset "start" to the end.
+pub(crate) fn parse_expr(code: &str, start: usize, line: usize, definitions: &Definitions) -> Result<Expr, SyntaxError> {
+    let mut parser = ExprParser { code, definitions, position: start, line, depth: 0 };
+    let expr = parser.parse_or()?;
+    if parser.position < code.len() {
+        return Err(parser.error(parser.position, "Unexpected \")\" without a matching \"(\"."));
+    }
+    Ok(expr)
+}
+
+// The queries of one or more files, and the lines that could not be parsed.
+#[derive(Debug, Clone, Default)]
+pub struct QuerySet {
+    pub queries: Vec<Query>,
+    pub errors: Vec<SyntaxError>,
+    // The definitions in effect at the end of the file.
+    pub definitions: Definitions,
+}
+
+impl QuerySet {
+    fn extend(&mut self, other: QuerySet) {
+        self.queries.extend(other.queries);
+        self.errors.extend(other.errors);
+    }
+
+    // A file included more than once, i.e. by two overlays, only adds its queries and errors once.
+    fn dedup(&mut self) {
+        let mut canonical: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut key = |source: &Path, span: Span| (canonical.entry(source.to_path_buf()).or_insert_with(|| canonical_path(source)).clone(), span);
+        let mut seen: HashSet<(PathBuf, Span)> = HashSet::new();
+        self.queries.retain(|query| seen.insert(key(&query.source, query.span)));
+        let mut seen: HashSet<(PathBuf, Span)> = HashSet::new();
+        self.errors.retain(|error| seen.insert(key(&error.source, error.span)));
+    }
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    // The queries and definitions of a file or directory.
+    Include,
+    // Only the definitions of a file or directory.
+    Import,
+}
+
+// "include PATH" or "import PATH", and the byte offset of the path.
+fn parse_directive(code: &str) -> Option<(Directive, usize)> {
+    let trimmed = code.trim_start();
+    let (directive, keyword) = [(Directive::Include, "include"), (Directive::Import, "import")].into_iter()
+        .find(|(_, keyword)| trimmed.strip_prefix(keyword).is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)))?;
+    let rest = &trimmed[keyword.len()..];
+    Some((directive, code.len() - rest.trim_start().len()))
+}
+
+// Reads files and the files and directories they include.
+#[derive(Debug, Default)]
+struct Loader {
+    // Definitions of the directory being analysed. They replace lists of the same name defined by
+    // an included directory, so an overlay decides who counts as privileged for its base pack.
+    root: Option<Definitions>,
+    // Canonical paths of the files and directories being read, outermost first, and the paths
+    // they were read as.
+    stack: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Loader {
+    fn parse_str(&mut self, source: &Path, content: &str, definitions: &Definitions, category: &str) -> QuerySet {
+        let mut set = QuerySet { definitions: definitions.clone(), ..Default::default() };
+        for (i, line) in content.lines().enumerate() {
+            let code = strip_comment(line);
+            let parsed = if let Some((directive, offset)) = parse_directive(code) {
+                let span = Span { line: i + 1, column: column(code, offset) };
+                self.include(source, directive, &code[offset..], span, category, &mut set).map(|_| None)
+            } else if is_definition(code) {
+                set.definitions.parse_definition(code, i + 1).map(|_| None)
+            } else {
+                parse_line(line, i + 1, &set.definitions)
+            };
+            match parsed {
+                Ok(Some(mut query)) => {
+                    query.source = source.to_path_buf();
+                    query.category = category.to_string();
+                    set.queries.push(query);
+                }
+                Ok(None) => {}
+                Err(mut e) => {
+                    e.source = source.to_path_buf();
+                    set.errors.push(e);
+                }
+            }
+        }
+        set
+    }
+
+    fn include(&mut self, source: &Path, directive: Directive, target: &str, span: Span, category: &str, set: &mut QuerySet) -> Result<(), SyntaxError> {
+        let target = target.trim().trim_matches('"');
+        if target.is_empty() {
+            return Err(SyntaxError::new(span.line, span.column, "Expected a file or directory, i.e. \"include ../base\" or \"import definitions.queries\".".to_string()));
+        }
+        // Paths are relative to the including file. "import defs" finds "defs.queries".
+        let mut path = source.parent().unwrap_or(Path::new("")).join(target);
+        if !path.exists() && path.with_extension("queries").is_file() {
+            path = path.with_extension("queries");
+        }
+        if !path.exists() {
+            return Err(SyntaxError::new(span.line, span.column, format!("\"{}\" could not be found.", path.display())));
+        }
+        let canonical = canonical_path(&path);
+        if let Some(start) = self.stack.iter().position(|(current, _)| *current == canonical) {
+            let cycle: Vec<String> = self.stack[start..].iter().map(|(_, read_as)| read_as.display().to_string()).chain(std::iter::once(path.display().to_string())).collect();
+            return Err(SyntaxError::new(span.line, span.column, format!("Include cycle: {}.", cycle.join(" -> "))));
+        }
+        let included = if path.is_dir() {
+            self.load_dir(&path, category, &set.definitions)
+        } else {
+            self.load_file(&path, category, &set.definitions)
+        };
+        let included = included.map_err(|e| SyntaxError::new(span.line, span.column, format!("\"{}\" could not be read. {}", path.display(), e)))?;
+        set.definitions.merge(&included.definitions);
+        set.errors.extend(included.errors);
+        if directive == Directive::Include {
+            set.queries.extend(included.queries);
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &Path, category: &str, definitions: &Definitions) -> Result<QuerySet> {
+        // A file that can't be read, or isn't UTF-8, is an error of its own and the other files
+        // are still read.
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                let mut error = SyntaxError::new(1, 1, format!("The file could not be read. {}.", e));
+                error.source = path.to_path_buf();
+                return Ok(QuerySet { definitions: definitions.clone(), errors: vec!(error), ..Default::default() });
+            }
+        };
+        if rule::is_rule_file(path) {
+            return Ok(rule::parse_str(path, &content, definitions, category));
+        }
+        self.stack.push((canonical_path(path), path.to_path_buf()));
+        let set = self.parse_str(path, &content, definitions, category);
+        self.stack.pop();
+        Ok(set)
+    }
+
+    // Every ".queries" and ".toml" file of a directory in file name order, and its subdirectories
+    // as categories. The definitions file is read first and its definitions are visible to every
+    // other file.
+    fn load_dir(&mut self, dir: &Path, category: &str, definitions: &Definitions) -> Result<QuerySet> {
+        self.stack.push((canonical_path(dir), dir.to_path_buf()));
+        let set = self.read_dir(dir, category, definitions);
+        self.stack.pop();
+        set
+    }
+
+    fn read_dir(&mut self, dir: &Path, category: &str, definitions: &Definitions) -> Result<QuerySet> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for query_file in fs::read_dir(dir)? {
+            paths.push(query_file?.path());
+        }
+        paths.sort();
+        let definitions_path = dir.join(DEFINITIONS_FILE);
+        let mut set = QuerySet { definitions: definitions.clone(), ..Default::default() };
+        if definitions_path.is_file() {
+            set = self.load_file(&definitions_path, category, definitions)?;
+        }
+        match &self.root {
+            Some(root) => set.definitions.merge(root),
+            None => self.root = Some(set.definitions.clone()),
+        }
+        let definitions = set.definitions.clone();
+        for path in paths.iter().filter(|path| **path != definitions_path) {
+            if path.is_dir() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let category = if category.is_empty() { name.to_string() } else { format!("{}/{}", category, name) };
+                set.extend(self.load_dir(path, &category, &definitions)?);
+            } else if path.is_file() && is_query_file(path) {
+                set.extend(self.load_file(path, category, &definitions)?);
+            }
+        }
+        Ok(set)
+    }
+}
+
+// Files a queries directory is made of. Other files, i.e. a README or editor backups, are ignored.
+pub fn is_query_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("queries")) || rule::is_rule_file(path)
+}
+
+// "definitions" are visible to every line, and the file's own definitions to the lines after them.
+// Includes are relative to the directory of "source".
+pub fn parse_str(source: &Path, content: &str, definitions: &Definitions) -> QuerySet {
+    let mut loader = Loader { root: Some(definitions.clone()), ..Default::default() };
+    loader.stack.push((canonical_path(source), source.to_path_buf()));
+    let mut set = loader.parse_str(source, content, definitions, "");
+    set.dedup();
+    set
+}
+
+pub fn parse_file(query_path: &Path, definitions: &Definitions) -> Result<QuerySet> {
+    let mut loader = Loader { root: Some(definitions.clone()), ..Default::default() };
+    let mut set = loader.load_file(query_path, "", definitions)?;
+    set.dedup();
+    Ok(set)
+}
+
+// Every query file in a directory and its subdirectories, in file name order.
+pub fn parse_dir(queries_path: &Path) -> Result<QuerySet> {
+    let mut set = Loader::default().load_dir(queries_path, "", &Definitions::default())?;
+    set.dedup();
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse("U -- Policy::Minimum password length::#<14 | Links::>Servers::enabled // PSOs cover the rest").unwrap().unwrap();
+        assert_eq!(query.severity, Severity::Undesirable);
+        assert_eq!(query.conditions()[0].field, Field::Policy);
+        assert_eq!(query.conditions()[0].values, vec!("Minimum password length", "#<14"));
+        assert_eq!(query.conditions()[1].span, Span { line: 1, column: 46 });
+        assert_eq!(query.to_string(), "Query Condition(s):\n\tPolicy::Minimum password length::#<14\n\tLinks::>Servers::enabled");
+        assert_eq!(Query::parse("U -- policy::<Set the intranet::<http://").unwrap().unwrap().conditions()[0].values[1], "<http://");
+        assert!(Query::parse("// It is best to configure the GPO to 14").unwrap().is_none());
+        assert!(Query::parse("   ").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let query = Query::parse("W -- !Links::Domain Controllers | (Policy::Debug programs::>Users || Policy::Debug programs::>Everyone) | Policy::Wired Network (IEEE 802.3) Policies::Enabled").unwrap().unwrap();
+        let Expr::And(exprs) = &query.expr else {
+            panic!("expected an and expression");
+        };
+        assert_eq!(exprs.len(), 3);
+        assert!(matches!(&exprs[0], Expr::Not(expr) if matches!(expr.as_ref(), Expr::Condition(condition) if condition.values == vec!("Domain Controllers"))));
+        assert!(matches!(&exprs[1], Expr::Or(branches) if branches.len() == 2));
+        assert_eq!(query.conditions()[1].span, Span { line: 1, column: 36 });
+        assert_eq!(query.conditions()[3].values[0], "Wired Network (IEEE 802.3) Policies");
+        assert_eq!(query.to_string(), "Query Condition(s):\n\t!Links::Domain Controllers\n\t(Policy::Debug programs::>Users || Policy::Debug programs::>Everyone)\n\tPolicy::Wired Network (IEEE 802.3) Policies::Enabled");
+
+        // "|" binds tighter than "||"
+        let query = Query::parse("U -- Name::A | Name::B || Name::C").unwrap().unwrap();
+        assert!(matches!(&query.expr, Expr::Or(branches) if matches!(&branches[0], Expr::And(_))));
+        assert_eq!(query.expr.to_string(), "Name::A | Name::B || Name::C");
+        let query = Query::parse("U -- !(Name::A || Name::B)").unwrap().unwrap();
+        assert_eq!(query.expr.to_string(), "!(Name::A || Name::B)");
+
+        // "|" and parentheses inside a pattern belong to the pattern
+        let query = Query::parse("U -- (Name::~/^(Default|Baseline) /i) | Filtering::~/-5\\d\\d$/").unwrap().unwrap();
+        assert_eq!(query.conditions().len(), 2);
+        assert_eq!(query.conditions()[0].values[0], "~/^(Default|Baseline) /i");
+
+        assert_eq!(Query::parse("U -- (Name::A || Name::B").unwrap_err().to_string(), ":1:6: This \"(\" is never closed.");
+        assert_eq!(Query::parse("U -- (Name::A) ) | Name::B").unwrap_err().to_string(), ":1:16: Unexpected \")\" without a matching \"(\".");
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let set = parse_str(Path::new("kerberos.queries"), "\
+// AES is the current ideal in kerberos encryption
+U -- Network security: Configure encryption types allowed for Kerberos::<DES
+U -- Policy::Minimum password length::#<14 | Links
+This is not a comment
+X -- Name::Default Domain Policy
+W -- Name::Default Domain Policy |
+U -- Policy::Debug programs::~/(Admins/
+U -- Name::~/Default/g
+U -- Policy::Minimum password length::#!14
+U -- Links::>Servers::enforcd
+", &Definitions::default());
+        assert!(set.queries.is_empty());
+        let errors: Vec<String> = set.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec!(
+            "kerberos.queries:2:6: Unknown field \"Network security: Configure encryption types allowed for Kerberos\". Conditions start with one of Name, Details, Links, Filtering, Applies, Delegation, Policy, AppLocker, SoftwareRestriction, FirewallRule, Firewall, Certificate, Preference, WMI.",
+            "kerberos.queries:3:46: Expected Links::Location or Links::Location::State but found 1 \"::\" separated segments.",
+            "kerberos.queries:4:1: Expected a flag followed by \"--\", i.e. \"U -- Policy::Value::Setting\". Comments start with \"//\".",
+            "kerberos.queries:5:1: Unknown flag \"X\". Expected D, U, W or M.",
+            "kerberos.queries:6:35: Expected a condition, i.e. \"Policy::Value::Setting\".",
+            "kerberos.queries:7:30: Invalid pattern: unclosed group.",
+            "kerberos.queries:8:12: Unknown pattern flag \"g\". Expected i, m, s or x.",
+            "kerberos.queries:9:39: Invalid numeric modifier \"#!14\". Expected \"#<\", \"#<=\", \"#>\" or \"#>=\" followed by a number or a date, i.e. \"#<14\".",
+            "kerberos.queries:10:23: Unknown link state \"enforcd\". Expected enforced, enabled, disabled or blocked, each optionally negated with \"!\".",
+        ));
+    }
+
+    #[test]
+    fn test_definitions() {
+        let mut project = Definitions::default();
+        project.insert("TIER0", vec!(">Administrators".to_string(), ">Domain Admins".to_string()));
+        let set = parse_str(Path::new("dc.queries"), "\
+let OPERATORS = [>Backup Operators, >Server Operators] // adjusted per client
+let PRIVILEGED = [$TIER0, $OPERATORS]
+U -- Policy::Debug programs::!subset[$PRIVILEGED, >System] | Name::~/^C$/
+U -- Name::ADMIN$
+U -- Policy::Run these programs::\\\\srv\\$share | Preference::Files::Path::C:\\$Recycle.Bin | Name::!$WINDOWS.~BT | Name::!$$TIER0
+U -- Policy::Debug programs::only[$TIER1]
+let 1X = [Users]
+let USERS [Users]
+let USERS = Users
+", &project);
+        assert_eq!(set.queries.len(), 3);
+        let conditions = set.queries[0].conditions();
+        assert_eq!(conditions[0].values[1], "!subset[>Administrators, >Domain Admins, >Backup Operators, >Server Operators, >System]");
+        assert_eq!(conditions[0].text, "Policy::Debug programs::!subset[$PRIVILEGED, >System]");
+        assert_eq!(conditions[1].values[0], "~/^C$/");
+        assert_eq!(set.queries[1].conditions()[0].values[0], "ADMIN$");
+        let literals: Vec<&str> = set.queries[2].conditions().iter().map(|condition| condition.values.last().unwrap().as_str()).collect();
+        assert_eq!(literals, vec!("\\\\srv\\$share", "C:\\$Recycle.Bin", "!$WINDOWS.~BT", "!$TIER0"));
+        assert_eq!(set.definitions.get("PRIVILEGED").unwrap().len(), 4);
+        assert!(project.get("PRIVILEGED").is_none());
+
+        let errors: Vec<String> = set.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec!(
+            "dc.queries:6:35: Unknown variable \"$TIER1\". Define it first, i.e. \"let TIER1 = [...]\". Write \"$$\" for a \"$\" in a value.",
+            "dc.queries:7:5: Expected a name of letters, digits and \"_\" after \"let\", i.e. \"let TIER0 = [Administrators, >Domain Admins]\".",
+            "dc.queries:8:11: Expected \"=\" after \"let USERS\", i.e. \"let TIER0 = [Administrators, >Domain Admins]\".",
+            "dc.queries:9:13: Expected a list in brackets, i.e. \"let TIER0 = [Administrators, >Domain Admins]\".",
+        ));
+    }
+
+    #[test]
+    fn test_includes() {
+        let root = std::env::temp_dir().join(format!("gpo-insight-includes-{}", std::process::id()));
+        let files = [
+            ("base/definitions.queries", "let TIER0 = [>Administrators]\n"),
+            ("base/dc/logon.queries", "U -- Policy::Allow log on locally::!subset[$TIER0]\n"),
+            ("overlay/definitions.queries", "let TIER0 = [>Administrators, >Tier0 Admins]\n"),
+            ("overlay/client.queries", "include ../base\nimport ../base/dc/logon\ninclude client // itself\ninclude missing.queries\nU -- Name::Client\n"),
+            ("overlay/loop/a.queries", "include b.queries\n"),
+            ("overlay/loop/b.queries", "include a.queries\n"),
+        ];
+        for (path, content) in files.iter() {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        // Only ".queries" and ".toml" files are read, and an unreadable one is an error of its own.
+        fs::write(root.join("overlay/README.md"), "Client queries\n").unwrap();
+        fs::write(root.join("overlay/client.queries~"), "U -- Name::Old\n").unwrap();
+        fs::write(root.join("overlay/Thumbs.db"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(root.join("overlay/binary.queries"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let set = parse_dir(&root.join("overlay")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(set.queries.len(), 2);
+        assert_eq!(set.queries[0].category, "dc");
+        assert_eq!(set.queries[0].conditions()[0].values[1], "!subset[>Administrators, >Tier0 Admins]");
+        assert_eq!(set.queries[1].category, "");
+        let errors: Vec<String> = set.errors.iter().map(|e| format!("{}:{}: {}", e.source.file_name().unwrap().to_string_lossy(), e.span.line, e.message)).collect();
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].starts_with("binary.queries:1: The file could not be read. "));
+        assert!(errors[1].starts_with("client.queries:3: Include cycle: ") && errors[1].ends_with("client.queries."));
+        assert!(errors[2].starts_with("client.queries:4: \"") && errors[2].ends_with("missing.queries\" could not be found."));
+        assert!(errors[3].starts_with("b.queries:1: Include cycle: ") && errors[3].ends_with("a.queries."));
+        assert!(errors[4].starts_with("a.queries:1: Include cycle: ") && errors[4].ends_with("b.queries."));
+    }
+
+    #[test]
+    fn test_parse_shipped_queries() {
+        let set = parse_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("queries")).unwrap();
+        let errors: Vec<String> = set.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, Vec::<String>::new());
+        assert!(!set.queries.is_empty());
+        assert!(set.definitions.get("TIER0").is_some());
+    }
+}
@@ -0,0 +1,257 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Rules read from ".toml" rule files.
+//
+// A rule file is a list of "[[rule]]" tables. Each rule has a stable id, a title, a severity, its
+// rationale, remediation and references, and one or more conditions written like the expression
+// after "--" in a ".queries" file. The conditions are alternatives: a rule is a single query that
+// matches when any of its conditions does, so it gives at most one finding per GPO, and the
+// finding carries the metadata of the rule.
+
+use core::fmt;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+use crate::query::{parse_expr, Definitions, Expr, Query, QuerySet, Severity, Span, SyntaxError};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rule {
+    pub id: String,
+    pub title: String,
+    pub rationale: String,
+    pub remediation: String,
+    pub references: Vec<String>,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rule: [{}] {}", self.id, self.title)?;
+        if !self.rationale.is_empty() {
+            write!(f, "\n\tRationale: {}", self.rationale.trim())?;
+        }
+        if !self.remediation.is_empty() {
+            write!(f, "\n\tRemediation: {}", self.remediation.trim())?;
+        }
+        for reference in self.references.iter() {
+            write!(f, "\n\tReference: {}", reference)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<RuleTable>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleTable {
+    id: Spanned<String>,
+    title: String,
+    severity: Spanned<String>,
+    #[serde(default, alias = "description")]
+    rationale: String,
+    #[serde(default)]
+    remediation: String,
+    #[serde(default)]
+    references: Vec<String>,
+    conditions: Vec<Spanned<String>>,
+}
+
+pub fn is_rule_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+// The line and column of a byte offset into the file.
+fn span_at(content: &str, offset: usize) -> Span {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Span { line: before.matches('\n').count() + 1, column: before[line_start..].chars().count() + 1 }
+}
+
+// The byte offset in the file of every character of a TOML string, and of its closing quote.
+// Escape sequences, line ending backslashes and "\r\n" are longer in the file than in the string.
+fn char_offsets(content: &str, range: Range<usize>) -> Vec<usize> {
+    let raw = &content[range.clone()];
+    let quote = if raw.starts_with('\'') { '\'' } else { '"' };
+    let delimiter = if raw.starts_with("\"\"\"") || raw.starts_with("\'\'\'") { 3 } else { 1 };
+    let mut chars = raw[delimiter..raw.len() - delimiter].char_indices().peekable();
+    // A newline right after the opening quotes of a multi-line string isn't part of it.
+    if delimiter == 3 {
+        chars.next_if(|(_, c)| *c == '\r');
+        chars.next_if(|(_, c)| *c == '\n');
+    }
+    let mut offsets: Vec<usize> = Vec::new();
+    while let Some((i, c)) = chars.next() {
+        if c == '\r' && chars.peek().is_some_and(|(_, c)| *c == '\n') {
+            continue;
+        }
+        if c == '\\' && quote == '"' {
+            match chars.next() {
+                Some((_, 'u')) => {
+                    chars.nth(3);
+                }
+                Some((_, 'U')) => {
+                    chars.nth(7);
+                }
+                Some((_, c)) if c.is_whitespace() => {
+                    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                    continue;
+                }
+                _ => (),
+            }
+        }
+        offsets.push(range.start + delimiter + i);
+    }
+    offsets.push(range.end - delimiter);
+    offsets
+}
+
+fn error_at(source: &Path, content: &str, range: Range<usize>, message: String) -> SyntaxError {
+    let span = span_at(content, range.start);
+    SyntaxError { source: source.to_path_buf(), span, message }
+}
+
+fn parse_rule(source: &Path, content: &str, table: RuleTable, definitions: &Definitions, category: &str, set: &mut QuerySet) -> Result<(), SyntaxError> {
+    let id = table.id.get_ref().trim();
+    if id.is_empty() {
+        return Err(error_at(source, content, table.id.span(), "Expected a rule id, i.e. \"id = \\\"KRB-001\\\"\".".to_string()));
+    }
+    let Some(severity) = Severity::from_name(table.severity.get_ref()) else {
+        return Err(error_at(source, content, table.severity.span(), format!("Unknown severity \"{}\". Expected desirable, undesirable, warning or missing.", table.severity.get_ref())));
+    };
+    if table.conditions.is_empty() {
+        return Err(error_at(source, content, table.id.span(), format!("The rule \"{}\" has no conditions.", id)));
+    }
+    let rule = Rule {
+        id: id.to_string(),
+        title: table.title.trim().to_string(),
+        rationale: table.rationale,
+        remediation: table.remediation,
+        references: table.references,
+    };
+    let mut exprs: Vec<Expr> = Vec::new();
+    for condition in table.conditions.iter() {
+        // The condition is parsed as it is after TOML unescaped it, and the columns in that
+        // string are moved to the characters they came from, so spans point into the rule file.
+        let offsets = char_offsets(content, condition.span());
+        let at = |span: Span| span_at(content, offsets[(span.column - 1).min(offsets.len() - 1)]);
+        let mut expr = parse_expr(condition.get_ref(), 0, 1, definitions).map_err(|mut e| {
+            e.source = source.to_path_buf();
+            e.span = at(e.span);
+            e
+        })?;
+        expr.map_spans(&at);
+        exprs.push(expr);
+    }
+    let expr = if exprs.len() == 1 { exprs.remove(0) } else { Expr::Or(exprs) };
+    set.queries.push(Query {
+        severity,
+        expr,
+        source: source.to_path_buf(),
+        span: span_at(content, char_offsets(content, table.conditions[0].span())[0]),
+        category: category.to_string(),
+        rule: Some(rule),
+    });
+    Ok(())
+}
+
+// The rules of a rule file as queries. "definitions" are visible to every condition.
+pub fn parse_str(source: &Path, content: &str, definitions: &Definitions, category: &str) -> QuerySet {
+    let mut set = QuerySet { definitions: definitions.clone(), ..Default::default() };
+    let file: RuleFile = match toml::from_str(content) {
+        Ok(file) => file,
+        Err(e) => {
+            let message = format!("{}.", e.message().trim_end_matches('.'));
+            set.errors.push(error_at(source, content, e.span().unwrap_or(0..0), message));
+            return set;
+        }
+    };
+    let mut ids: HashSet<String> = HashSet::new();
+    for table in file.rule {
+        if !ids.insert(table.id.get_ref().trim().to_lowercase()) {
+            set.errors.push(error_at(source, content, table.id.span(), format!("The rule id \"{}\" is used more than once.", table.id.get_ref())));
+            continue;
+        }
+        if let Err(e) = parse_rule(source, content, table, definitions, category, &mut set) {
+            set.errors.push(e);
+        }
+    }
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"
+[[rule]]
+id = "KRB-001"
+title = "DES is allowed for Kerberos"
+severity = "undesirable"
+description = "DES keys can be cracked in hours."
+remediation = "Only allow AES128 and AES256."
+references = ["https://example.org/kerberos"]
+conditions = [
+    "Policy::Network security: Configure encryption types allowed for Kerberos::<DES",
+    "Policy::Network security: Configure encryption types allowed for Kerberos::$WEAK",
+]
+"#;
+
+    #[test]
+    fn test_parse_rules() {
+        let mut definitions = Definitions::default();
+        definitions.insert("WEAK", vec!("<RC4".to_string()));
+        let set = parse_str(Path::new("kerberos.toml"), RULES, &definitions, "dc");
+        assert!(set.errors.is_empty(), "{:?}", set.errors);
+        assert_eq!(set.queries.len(), 1);
+        assert_eq!(set.queries[0].severity, Severity::Undesirable);
+        assert_eq!(set.queries[0].category, "dc");
+        assert_eq!(set.queries[0].span, Span { line: 10, column: 6 });
+        assert_eq!(set.queries[0].conditions()[0].span, Span { line: 10, column: 6 });
+        assert_eq!(set.queries[0].conditions()[1].span, Span { line: 11, column: 6 });
+        assert_eq!(set.queries[0].conditions()[1].values[1], "<RC4");
+        assert!(matches!(set.queries[0].expr, Expr::Or(_)));
+        let rule = set.queries[0].rule.as_ref().unwrap();
+        assert_eq!(rule.rationale, "DES keys can be cracked in hours.");
+        assert_eq!(rule.to_string(), "Rule: [KRB-001] DES is allowed for Kerberos\n\tRationale: DES keys can be cracked in hours.\n\tRemediation: Only allow AES128 and AES256.\n\tReference: https://example.org/kerberos");
+        assert!(is_rule_file(Path::new("queries/Kerberos.TOML")));
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        let errors = |content: &str| -> Vec<String> {
+            parse_str(Path::new("rules.toml"), content, &Definitions::default(), "").errors.iter().map(|e| e.to_string()).collect()
+        };
+        assert_eq!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"bad\"\nconditions = [\"Name::A\"]\n"), vec!("rules.toml:4:12: Unknown severity \"bad\". Expected desirable, undesirable, warning or missing."));
+        assert_eq!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = [\"Nme::A\"]\n")[0].split(": ").next().unwrap(), "rules.toml:5:16");
+        // Escapes before the error are longer in the file than in the condition.
+        assert_eq!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = [\"Policy::\\\"A\\\\B\\\"::C | Nme::A\"]\n")[0].split(": ").next().unwrap(), "rules.toml:5:38");
+        assert_eq!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = [\"\"\"\nName::A |\n  Nme::A\"\"\"]\n")[0].split(": ").next().unwrap(), "rules.toml:7:3");
+        assert_eq!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = []\n"), vec!("rules.toml:2:6: The rule \"A\" has no conditions."));
+        assert!(errors("[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = [\"Name::A\"]\nowner = \"me\"\n")[0].starts_with("rules.toml:6:1: unknown field `owner`"));
+        assert!(errors("[[rule]]\nid = \"A\"\nseverity = \"W\"\nconditions = [\"Name::A\"]\n")[0].contains("missing field `title`"));
+        assert!(errors("[[rule]\n")[0].starts_with("rules.toml:1:"));
+        let duplicate = "[[rule]]\nid = \"A\"\ntitle = \"A\"\nseverity = \"W\"\nconditions = [\"Name::A\"]\n";
+        assert_eq!(errors(&format!("{}{}", duplicate, duplicate)), vec!("rules.toml:7:6: The rule id \"A\" is used more than once."));
+    }
+}
@@ -0,0 +1,349 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Typed values for the "Setting" cell of a policy.
+//
+// Report cells are free text such as "14 characters", "42 days", "Enabled", "Not Defined" or
+// "BUILTIN\Administrators, LABS\Domain Admins". Parsing them lets numeric query modifiers compare
+// "#>=30 days" against "6 weeks" or "#<14" against "7 characters".
+
+use core::cmp::Ordering;
+use core::fmt;
+use chrono::{Months, NaiveDateTime, TimeDelta};
+use serde::Serialize;
+use crate::principal::Principal;
+use crate::table;
+
+// Report sections whose settings are lists of users and groups.
+const PRINCIPAL_SECTIONS: &[&str] = &["user rights assignment", "restricted groups"];
+
+// Policies outside those sections whose setting is a comma separated list of values.
+const LIST_POLICIES: &[&str] = &[
+    "network security: configure encryption types allowed for kerberos",
+    "network access: named pipes that can be accessed anonymously",
+    "network access: shares that can be accessed anonymously",
+    "network access: remotely accessible registry paths",
+    "network access: remotely accessible registry paths and subpaths",
+];
+
+// Units of time printed by the report and their length in seconds.
+const DURATION_UNITS: &[(&str, i64)] = &[
+    ("second", 1),
+    ("minute", 60),
+    ("hour", 3600),
+    ("day", 86400),
+    ("week", 604800),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SettingValue {
+    // A count with an optional unit, i.e. "14 characters" or "0".
+    Number(i64, String),
+    // A length of time kept in the unit it was printed in, i.e. "42 days".
+    Duration(i64, String),
+    // "Enabled", "Disabled", "Yes", "No", "True" or "False".
+    State(bool),
+    Principals(Vec<Principal>),
+    List(Vec<String>),
+    NotDefined,
+    Text(String),
+}
+
+// Sections are printed with their parent, i.e. "Local Policies/User Rights Assignment".
+pub fn is_principal_section(section: &str) -> bool {
+    let section = section.trim().to_lowercase();
+    let section = section.rsplit('/').next().unwrap_or_default();
+    PRINCIPAL_SECTIONS.contains(&section)
+}
+
+fn duration_unit(unit: &str) -> Option<i64> {
+    let unit = unit.to_lowercase();
+    let unit = unit.strip_suffix('s').unwrap_or(&unit);
+    DURATION_UNITS.iter().find(|(name, _)| *name == unit).map(|(_, seconds)| *seconds)
+}
+
+impl SettingValue {
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        let lowered = value.to_lowercase();
+        match lowered.as_str() {
+            "not defined" | "not configured" => return Self::NotDefined,
+            "enabled" | "yes" | "true" => return Self::State(true),
+            "disabled" | "no" | "false" => return Self::State(false),
+            _ => {}
+        }
+
+        let (number, unit) = match value.split_once(' ') {
+            Some((number, unit)) => (number, unit.trim()),
+            None => (value, ""),
+        };
+        match number.parse::<i64>() {
+            Ok(number) if unit.chars().all(|c| c.is_alphabetic() || c == ' ') => {
+                match unit.split(' ').next().and_then(duration_unit) {
+                    Some(_) => Self::Duration(number, unit.to_string()),
+                    None => Self::Number(number, unit.to_string()),
+                }
+            }
+            _ => Self::Text(value.to_string()),
+        }
+    }
+
+    // Only settings known to hold a list are split on commas. Everything else keeps its commas,
+    // i.e. "Send NTLMv2 response only. Refuse LM & NTLM" or "OU=Workstations,DC=labs,DC=local".
+    pub fn parse_policy(section: &str, policy: &str, value: &str) -> Self {
+        let items = value.split(',').map(|item| item.trim()).filter(|item| !item.is_empty());
+        if is_principal_section(section) {
+            Self::Principals(items.map(Principal::parse).collect())
+        } else if LIST_POLICIES.contains(&policy.trim().to_lowercase().as_str()) {
+            Self::List(items.map(|item| item.to_string()).collect())
+        } else {
+            Self::parse(value)
+        }
+    }
+
+    // The members of a list of values. Principals are matched through their own forms instead.
+    pub fn items(&self) -> &[String] {
+        match self {
+            Self::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn principals(&self) -> &[Principal] {
+        match self {
+            Self::Principals(principals) => principals,
+            _ => &[],
+        }
+    }
+
+    fn seconds(&self) -> Option<i64> {
+        match self {
+            Self::Duration(amount, unit) => unit.split(' ').next().and_then(duration_unit).map(|seconds| amount * seconds),
+            _ => None,
+        }
+    }
+
+    // Durations compare across units. A bare number compares against the number printed in the cell.
+    pub fn compare(&self, operand: &SettingValue) -> Option<Ordering> {
+        match (self, operand) {
+            (Self::Duration(..), Self::Duration(..)) => Some(self.seconds()?.cmp(&operand.seconds()?)),
+            (Self::Duration(amount, _), Self::Number(number, unit)) | (Self::Number(amount, _), Self::Number(number, unit)) if unit.is_empty() => Some(amount.cmp(number)),
+            (Self::Number(amount, current), Self::Number(number, unit)) if current.eq_ignore_ascii_case(unit) => Some(amount.cmp(number)),
+            _ => None,
+        }
+    }
+
+    // Numeric query modifiers: "#>=", "#>", "#<=" and "#<" followed by a number and optional unit.
+    pub fn matches_numeric(&self, query: &str) -> Option<bool> {
+        for (modifier, accepted) in [("#>=", &[Ordering::Greater, Ordering::Equal][..]), ("#>", &[Ordering::Greater][..]), ("#<=", &[Ordering::Less, Ordering::Equal][..]), ("#<", &[Ordering::Less][..])] {
+            if let Some(operand) = query.strip_prefix(modifier) {
+                return Some(match self.compare(&Self::parse(operand)) {
+                    Some(ordering) => accepted.contains(&ordering),
+                    None => false,
+                });
+            }
+        }
+        None
+    }
+}
+
+// The moment something reached an age, i.e. three years before now for "3 years". Months and
+// years are calendar months, the other units are fixed lengths.
+fn age_cutoff(age: &str, now: &NaiveDateTime) -> Option<NaiveDateTime> {
+    let (number, unit) = age.trim().split_once(' ')?;
+    let number: u32 = number.parse().ok()?;
+    let unit = unit.trim().to_lowercase();
+    let unit = unit.strip_suffix('s').unwrap_or(&unit);
+    match unit {
+        "month" => now.checked_sub_months(Months::new(number)),
+        "year" => now.checked_sub_months(Months::new(number.checked_mul(12)?)),
+        _ => now.checked_sub_signed(TimeDelta::try_seconds(i64::from(number) * duration_unit(unit)?)?),
+    }
+}
+
+// Date query modifiers: "<", ">", "<=" and ">=" followed by a date, i.e. "<2020-01-01", or an age,
+// i.e. "older than 3 years" or "newer than 90 days". A "#" in front of the comparison is accepted
+// like the numeric modifiers. Anything else isn't a date comparison and is None.
+pub fn matches_date(date: Option<NaiveDateTime>, query: &str, now: &NaiveDateTime) -> Option<bool> {
+    let query = query.trim();
+    let lowered = query.to_lowercase();
+    let (accepted, operand): (&[Ordering], NaiveDateTime) = if let Some(age) = lowered.strip_prefix("older than ") {
+        (&[Ordering::Less], age_cutoff(age, now)?)
+    } else if let Some(age) = lowered.strip_prefix("newer than ") {
+        (&[Ordering::Greater], age_cutoff(age, now)?)
+    } else {
+        let query = query.strip_prefix('#').unwrap_or(query);
+        let (modifier, accepted) = [(">=", &[Ordering::Greater, Ordering::Equal][..]), (">", &[Ordering::Greater][..]), ("<=", &[Ordering::Less, Ordering::Equal][..]), ("<", &[Ordering::Less][..])]
+            .into_iter()
+            .find(|(modifier, _)| query.starts_with(modifier))?;
+        (accepted, table::parse_date(&query[modifier.len()..])?)
+    };
+    // A GPO without the date can't be older or newer than anything.
+    Some(date.map(|date| accepted.contains(&date.cmp(&operand))).unwrap_or(false))
+}
+
+// Whether a value starting with "#" is a comparison the numeric or date modifiers understand,
+// i.e. "#<14", "#>=30 days" or "#>2021-03-01".
+pub fn is_comparison(query: &str) -> bool {
+    let Some(rest) = query.trim().strip_prefix('#') else {
+        return false;
+    };
+    let Some(operand) = [">=", ">", "<=", "<"].iter().find_map(|modifier| rest.strip_prefix(modifier)) else {
+        return false;
+    };
+    matches!(SettingValue::parse(operand), SettingValue::Number(..) | SettingValue::Duration(..)) || table::parse_date(operand).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOperator {
+    // Every member is one of the entries, and there is at least one member.
+    Only,
+    // Every member is one of the entries. An empty list is a subset as well.
+    Subset,
+    // Every member is one of the entries, and every entry is a member.
+    Exactly,
+    // No member is one of the entries.
+    None,
+}
+
+// A set operator over the members of a list, i.e. "only[BUILTIN\Administrators, >Domain Admins]".
+// Entries use the same modifiers as any other value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQuery {
+    pub operator: ListOperator,
+    pub entries: Vec<String>,
+}
+
+impl ListQuery {
+    // "only[...]", "exactly[...]", "subset[...]" or "none[...]". "subset of [...]" and "none of [...]" read better.
+    pub fn parse(value: &str) -> Option<Self> {
+        let lowered = value.trim().to_lowercase();
+        let (operator, rest) = [("only", ListOperator::Only), ("exactly", ListOperator::Exactly), ("subset", ListOperator::Subset), ("none", ListOperator::None)].into_iter()
+            .find_map(|(keyword, operator)| lowered.strip_prefix(keyword).map(|rest| (operator, rest)))?;
+        let rest = rest.trim_start();
+        let rest = rest.strip_prefix("of").unwrap_or(rest).trim_start();
+        if !rest.starts_with('[') || !rest.ends_with(']') {
+            return None;
+        }
+        // Keep the case of the entries.
+        let start = value.find('[')?;
+        let end = value.rfind(']')?;
+        Some(Self {
+            operator,
+            entries: value[start + 1..end].split(',').map(|entry| entry.trim().to_string()).filter(|entry| !entry.is_empty()).collect(),
+        })
+    }
+
+    // The members that break the operator, and for "exactly" the entries without a member.
+    pub fn offending<T: fmt::Display>(&self, members: &[T], matches: impl Fn(&T, &str) -> bool) -> Vec<String> {
+        let listed = |member: &T| self.entries.iter().any(|entry| matches(member, entry));
+        match self.operator {
+            ListOperator::None => members.iter().filter(|member| listed(member)).map(|member| member.to_string()).collect(),
+            _ => {
+                let mut offending: Vec<String> = members.iter().filter(|member| !listed(member)).map(|member| member.to_string()).collect();
+                if self.operator == ListOperator::Exactly {
+                    for entry in self.entries.iter().filter(|entry| !members.iter().any(|member| matches(member, entry))) {
+                        offending.push(format!("Missing {}", entry));
+                    }
+                }
+                offending
+            }
+        }
+    }
+
+    pub fn is_match<T: fmt::Display>(&self, members: &[T], matches: impl Fn(&T, &str) -> bool) -> bool {
+        if self.operator == ListOperator::Only && members.is_empty() {
+            return false;
+        }
+        self.offending(members, matches).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_setting() {
+        assert_eq!(SettingValue::parse("14 characters"), SettingValue::Number(14, "characters".to_string()));
+        assert_eq!(SettingValue::parse("42 days"), SettingValue::Duration(42, "days".to_string()));
+        assert_eq!(SettingValue::parse("Enabled"), SettingValue::State(true));
+        assert_eq!(SettingValue::parse("Not Defined"), SettingValue::NotDefined);
+        assert_eq!(SettingValue::parse_policy("Local Policies/Security Options", "Network security: Configure encryption types allowed for Kerberos", "AES128_HMAC_SHA1, AES256_HMAC_SHA1").items(), ["AES128_HMAC_SHA1", "AES256_HMAC_SHA1"]);
+        assert_eq!(SettingValue::parse_policy("Local Policies/Security Options", "Interactive logon: Message text", "Hello, world"), SettingValue::Text("Hello, world".to_string()));
+        assert_eq!(SettingValue::parse("Send NTLMv2 response only. Refuse LM & NTLM"), SettingValue::Text("Send NTLMv2 response only. Refuse LM & NTLM".to_string()));
+        assert_eq!(SettingValue::parse_policy("Local Policies/User Rights Assignment", "Debug programs", "BUILTIN\\Administrators, LABS\\Domain Admins"), SettingValue::Principals(vec!(Principal::parse("BUILTIN\\Administrators"), Principal::parse("LABS\\Domain Admins"))));
+    }
+
+    #[test]
+    fn test_numeric_setting() {
+        let length = SettingValue::parse("14 characters");
+        assert_eq!(length.matches_numeric("#>=14"), Some(true));
+        assert_eq!(length.matches_numeric("#>14"), Some(false));
+        assert_eq!(length.matches_numeric("#<15 characters"), Some(true));
+        assert_eq!(length.matches_numeric("Enabled"), None);
+
+        let age = SettingValue::parse("42 days");
+        assert_eq!(age.matches_numeric("#>6 weeks"), Some(false));
+        assert_eq!(age.matches_numeric("#>=6 weeks"), Some(true));
+        assert_eq!(age.matches_numeric("#<1000 hours"), Some(false));
+        assert_eq!(age.matches_numeric("#>30"), Some(true));
+        assert_eq!(SettingValue::parse("Enabled").matches_numeric("#>0"), Some(false));
+        assert!(is_comparison("#>=30 days") && is_comparison("#<2021-03-01"));
+        assert!(!is_comparison("#!14") && !is_comparison("#<") && !is_comparison("#=14"));
+    }
+
+    #[test]
+    fn test_date_query() {
+        let now = table::parse_date("2024-06-15 12:00:00").unwrap();
+        let modified = table::parse_date("3/1/2021 9:30:00 AM");
+        assert_eq!(matches_date(modified, "<2022-01-01", &now), Some(true));
+        assert_eq!(matches_date(modified, "#>=2021-03-01", &now), Some(true));
+        assert_eq!(matches_date(modified, ">2021-03-01 09:30:00", &now), Some(false));
+        assert_eq!(matches_date(modified, "older than 3 years", &now), Some(true));
+        assert_eq!(matches_date(modified, "Older Than 4 years", &now), Some(false));
+        assert_eq!(matches_date(modified, "newer than 200 weeks", &now), Some(true));
+        assert_eq!(matches_date(modified, "older than 40 months", &now), Some(false));
+        assert_eq!(matches_date(None, "older than 1 day", &now), Some(false));
+        assert_eq!(matches_date(modified, "<2021", &now), None);
+        assert_eq!(matches_date(modified, "older than a while", &now), None);
+    }
+
+    #[test]
+    fn test_list_query() {
+        let list = ListQuery::parse("only[BUILTIN\\Administrators, >Domain Admins]").unwrap();
+        assert_eq!(list.operator, ListOperator::Only);
+        assert_eq!(list.entries, vec!("BUILTIN\\Administrators", ">Domain Admins"));
+        assert_eq!(ListQuery::parse("None of [Users, Everyone]").unwrap().operator, ListOperator::None);
+        assert!(ListQuery::parse("only admins").is_none());
+        assert!(ListQuery::parse("Nonexistent[Users]").is_none());
+
+        let matches = |member: &Principal, entry: &str| member.matches(entry);
+        let admins = vec!(Principal::parse("BUILTIN\\Administrators"), Principal::parse("LABS\\Domain Admins"));
+        let mixed = vec!(Principal::parse("BUILTIN\\Administrators"), Principal::parse("BUILTIN\\Users"));
+        assert!(list.is_match(&admins, matches));
+        assert!(!list.is_match(&mixed, matches));
+        assert_eq!(list.offending(&mixed, matches), vec!("BUILTIN\\Users"));
+        assert!(!list.is_match(&Vec::<Principal>::new(), matches));
+        assert!(ListQuery::parse("subset[Administrators]").unwrap().is_match(&Vec::<Principal>::new(), matches));
+
+        let exactly = ListQuery::parse("exactly[Administrators, Backup Operators]").unwrap();
+        assert_eq!(exactly.offending(&admins, matches), vec!("LABS\\Domain Admins", "Missing Backup Operators"));
+        let none = ListQuery::parse("none of [Users, Everyone]").unwrap();
+        assert_eq!(none.offending(&mixed, matches), vec!("BUILTIN\\Users"));
+        assert!(none.is_match(&admins, matches));
+    }
+}
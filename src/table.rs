@@ -0,0 +1,226 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Helpers for the box-drawing tables html2text produces from the GPO report.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+// Headings of the report sections that group settings. A section parser stops reading
+// when it reaches a heading that belongs to another section.
+const SECTION_HEADINGS: &[&str] = &[
+    "computer configuration",
+    "user configuration",
+    "policies",
+    "windows settings",
+    "security settings",
+    "account policies",
+    "local policies",
+    "event log",
+    "restricted groups",
+    "system services",
+    "registry",
+    "file system",
+    "wired network (ieee 802.3) policies",
+    "windows firewall with advanced security",
+    "windows defender firewall with advanced security",
+    "network list manager policies",
+    "wireless network (ieee 802.11) policies",
+    "public key policies",
+    "software restriction policies",
+    "application control policies",
+    "ip security policies on active directory",
+    "advanced audit configuration",
+    "administrative templates",
+    "extra registry settings",
+    "preferences",
+    "control panel settings",
+    "scripts",
+    "folder redirection",
+    "deployed printers",
+    "policy-based qos",
+];
+
+// Split a table row into its trimmed cells.
+pub fn split_row(line: &str) -> Vec<String> {
+    line.split('│').map(|cell| cell.trim().to_string()).collect()
+}
+
+// Rows are cell values separated by "│". Headings and prose have no separator.
+pub fn is_row(line: &str) -> bool {
+    line.contains('│')
+}
+
+// Horizontal rules drawn above and below table headers.
+pub fn is_border(line: &str) -> bool {
+    line.contains("────")
+}
+
+// html2text wraps long cells onto extra lines and draws a border below every row, so the lines
+// between two borders are a single row. Fold them back together so each row is a single line.
+// A word longer than its column is broken across lines and is joined back without a space.
+pub fn unwrap_rows(content: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut group: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        if is_row(line) {
+            group.push(line);
+            continue;
+        }
+        // Rows that are not closed by a border, or whose lines have different numbers of cells,
+        // are left as they are.
+        let cells = group.first().map(|row| row.matches('│').count());
+        if is_border(line) && group.len() > 1 && group.iter().all(|row| Some(row.matches('│').count()) == cells) {
+            lines.push(join_row(&group));
+        } else {
+            lines.extend(group.iter().map(|row| row.to_string()));
+        }
+        group.clear();
+        lines.push(line.to_string());
+    }
+    lines.extend(group.iter().map(|row| row.to_string()));
+    lines.join("\n")
+}
+
+fn join_row(group: &[&str]) -> String {
+    let mut cells: Vec<String> = Vec::new();
+    // Whether the fragment last added to a cell was a single word filling the whole column.
+    let mut broken: Vec<bool> = Vec::new();
+    for line in group {
+        for (index, raw) in line.split('│').enumerate() {
+            if cells.len() <= index {
+                cells.push(String::new());
+                broken.push(false);
+            }
+            let fragment = raw.trim();
+            if fragment.is_empty() {
+                continue;
+            }
+            if !cells[index].is_empty() && !broken[index] {
+                cells[index].push(' ');
+            }
+            cells[index].push_str(fragment);
+            broken[index] = !raw.ends_with(char::is_whitespace) && !fragment.contains(char::is_whitespace);
+        }
+    }
+    cells.join("│")
+}
+
+// Section headings may carry a status suffix, e.g. "Computer Configuration (Enabled)".
+pub fn is_section_heading(line: &str) -> bool {
+    if is_row(line) {
+        return false;
+    }
+    let heading = line.trim().to_lowercase();
+    let heading = match heading.find(" (") {
+        Some(i) if heading.ends_with(')') => heading[..i].to_string(),
+        _ => heading,
+    };
+    SECTION_HEADINGS.contains(&heading.as_str())
+}
+
+// Find the index of the first header cell matching any of the given names.
+pub fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    header.iter().position(|cell| names.iter().any(|name| cell.eq_ignore_ascii_case(name)))
+}
+
+// Get a cell by optional column index, returning an empty string when absent.
+pub fn cell(row: &[String], index: Option<usize>) -> String {
+    match index {
+        Some(i) => row.get(i).cloned().unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+// Dates are printed in the locale of the machine that ran Get-GPOReport, usually "8/23/2023 3:28:14 PM".
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    for format in ["%m/%d/%Y %I:%M:%S %p", "%m/%d/%Y %H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%d/%m/%Y %H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date);
+        }
+    }
+    for format in ["%m/%d/%Y", "%Y-%m-%d", "%d/%m/%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return date.and_hms_opt(0, 0, 0);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_row() {
+        let row = split_row("Allow   │ Everyone │ %OSDRIVE%\\*  ");
+        assert_eq!(row, vec!("Allow", "Everyone", "%OSDRIVE%\\*"));
+        assert_eq!(column(&row, &["everyone"]), Some(1));
+        assert_eq!(cell(&row, Some(5)), "");
+    }
+
+    #[test]
+    fn test_unwrap_rows() {
+        // Output of html2text for a table with wrapped cells
+        let content = "\
+──────────────────────────┬──────────────────────────────────
+Policy                    │Setting                           
+──────────────────────────┼──────────────────────────────────
+Network security:         │AES128_HMAC_SHA1, AES256_HMAC_SHA1
+Configure encryption types│Future encryption types           
+allowed for Kerberos      │                                  
+──────────────────────────┼──────────────────────────────────
+Specify intranet service  │http://wsus.labs.local:8530/Selfup
+                          │date                              
+──────────────────────────┼──────────────────────────────────
+Minimum password length   │14 characters                     
+──────────────────────────┴──────────────────────────────────
+Allow    │Everyone│Allow the Temp folder│Path
+Path     │C:\\Windows\\Temp\\*
+─────────┴────────────────────
+Stale rule │
+Enabled │ False
+";
+        let unwrapped = unwrap_rows(content);
+        let rows: Vec<Vec<String>> = unwrapped.lines().filter(|line| is_row(line)).map(split_row).collect();
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[1], vec!("Network security: Configure encryption types allowed for Kerberos", "AES128_HMAC_SHA1, AES256_HMAC_SHA1 Future encryption types"));
+        assert_eq!(rows[2][1], "http://wsus.labs.local:8530/Selfupdate");
+        assert_eq!(rows[3][1], "14 characters");
+        // Lines with different numbers of cells are separate rows
+        assert_eq!(rows[4].len(), 4);
+        assert_eq!(rows[5], vec!("Path", "C:\\Windows\\Temp\\*"));
+        // Rows without borders are not joined
+        assert_eq!(rows[6], vec!("Stale rule", ""));
+    }
+
+    #[test]
+    fn test_section_heading() {
+        assert!(is_section_heading("Computer Configuration (Enabled)"));
+        assert!(is_section_heading("Public Key Policies"));
+        assert!(is_section_heading("Wireless Network (IEEE 802.11) Policies"));
+        assert!(!is_section_heading("Inbound Rules"));
+        assert!(!is_section_heading("Registry │ HKLM"));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let date = parse_date("8/23/2023 3:28:14 PM").unwrap();
+        assert_eq!(date.to_string(), "2023-08-23 15:28:14");
+        assert_eq!(parse_date("2020-01-01").unwrap().to_string(), "2020-01-01 00:00:00");
+        assert!(parse_date("Never").is_none());
+    }
+}
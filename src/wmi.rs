@@ -0,0 +1,365 @@
+// This file is a part of Audit-Inspector
+// Copyright (C) 2024 Kiersten Gross
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// WMI filters linked to a GPO and a readable summary of the platforms their WQL targets.
+//
+// The filter is printed as "WMI Filter Name │ <name>" and "Description │ <text>" rows, followed by
+// its queries as "Query │ root\CIMv2; SELECT ..." rows or a "Namespace │ Query" table.
+
+use core::fmt;
+use std::collections::HashSet;
+use serde::Serialize;
+use crate::gpo::matches_value;
+use crate::table;
+
+// A single "Property OPERATOR Value" comparison from a WHERE clause.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WqlCondition {
+    pub property: String,
+    pub operator: String,
+    pub value: String,
+}
+
+// How the conditions of a WHERE clause are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum WqlLogic {
+    All,
+    Any,
+    // Both AND and OR, so the grouping matters.
+    Mixed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WqlQuery {
+    pub namespace: String,
+    pub query: String,
+    pub class: String,
+    pub logic: WqlLogic,
+    pub conditions: Vec<WqlCondition>,
+}
+
+impl WqlQuery {
+    pub fn parse(namespace: &str, query: &str) -> Self {
+        let mut wql = Self {
+            namespace: namespace.to_string(),
+            query: query.to_string(),
+            class: String::new(),
+            logic: WqlLogic::All,
+            conditions: Vec::new(),
+        };
+        let lowered = query.to_lowercase();
+        let Some(from) = lowered.find(" from ") else {
+            return wql;
+        };
+        let rest = &query[from + " from ".len()..];
+        let (class, clause) = match rest.to_lowercase().find(" where ") {
+            Some(i) => (&rest[..i], &rest[i + " where ".len()..]),
+            None => (rest, ""),
+        };
+        wql.class = class.trim().to_string();
+        let (terms, logic) = split_terms(clause);
+        wql.logic = logic;
+        for term in terms {
+            if let Some(condition) = parse_condition(&term) {
+                wql.conditions.push(condition);
+            }
+        }
+        wql
+    }
+
+    // Describe what the query targets, i.e. "workstations, Windows 10+". The parts of the summary
+    // all apply, so a clause using OR is only summarised when it picks product types, and the raw
+    // WQL is used for anything else.
+    pub fn summary(&self) -> Vec<String> {
+        let product_types_only = self.conditions.iter().all(|condition| condition.property.eq_ignore_ascii_case("producttype") && condition.operator == "=");
+        if self.logic == WqlLogic::Mixed || (self.logic == WqlLogic::Any && !product_types_only) {
+            return vec!(self.query.clone());
+        }
+        let mut parts: Vec<String> = Vec::new();
+        let mut product_types: Vec<&str> = Vec::new();
+        for condition in self.conditions.iter() {
+            let property = condition.property.to_lowercase();
+            let operator = condition.operator.as_str();
+            if property == "producttype" {
+                let all = ["workstations", "domain controllers", "servers"];
+                let named = match condition.value.as_str() {
+                    "1" => Some("workstations"),
+                    "2" => Some("domain controllers"),
+                    "3" => Some("servers"),
+                    _ => None,
+                };
+                match (operator, named) {
+                    ("=", Some(name)) => product_types.push(name),
+                    ("<>", Some(name)) | ("!=", Some(name)) => product_types.extend(all.iter().filter(|n| **n != name)),
+                    _ => parts.push(format!("ProductType {} {}", operator, condition.value)),
+                }
+            }
+            else if property == "version" {
+                parts.push(describe_version(operator, &condition.value));
+            }
+            else if property == "buildnumber" {
+                parts.push(format!("build {} {}", operator.to_lowercase(), condition.value));
+            }
+            else if property == "osarchitecture" {
+                parts.push(condition.value.clone());
+            }
+            else if property == "pcsystemtype" && condition.value == "2" && operator == "=" {
+                parts.push("laptops".to_string());
+            }
+            else {
+                parts.push(format!("{} {} {}", condition.property, operator.to_lowercase(), condition.value));
+            }
+        }
+        product_types.dedup();
+        if !product_types.is_empty() {
+            parts.insert(0, product_types.join(" and "));
+        }
+        parts
+    }
+}
+
+// Split a WHERE clause on AND/OR, ignoring grouping parentheses and quoted text.
+fn split_terms(clause: &str) -> (Vec<String>, WqlLogic) {
+    let mut terms: Vec<String> = Vec::new();
+    let (mut and, mut or) = (false, false);
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for word in clause.split_whitespace() {
+        for c in word.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                None if c == '"' || c == '\'' => quote = Some(c),
+                _ => {}
+            }
+        }
+        let keyword = word.to_lowercase();
+        if quote.is_none() && (keyword == "and" || keyword == "or") {
+            and |= keyword == "and";
+            or |= keyword == "or";
+            terms.push(current.clone());
+            current = String::new();
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+    }
+    terms.push(current);
+    let logic = match (and, or) {
+        (true, true) => WqlLogic::Mixed,
+        (false, true) => WqlLogic::Any,
+        _ => WqlLogic::All,
+    };
+    (terms.into_iter().map(|term| term.trim_matches(|c| c == '(' || c == ')' || c == ' ').to_string()).filter(|term| !term.is_empty()).collect(), logic)
+}
+
+fn parse_condition(term: &str) -> Option<WqlCondition> {
+    let lowered = term.to_lowercase();
+    let (index, operator) = [" not like ", " like ", ">=", "<=", "<>", "!=", "=", ">", "<"].iter()
+        .filter_map(|operator| lowered.find(operator).map(|i| (i, *operator)))
+        .min_by_key(|(i, operator)| (*i, usize::MAX - operator.len()))?;
+    Some(WqlCondition {
+        property: term[..index].trim().to_string(),
+        operator: operator.trim().to_uppercase(),
+        value: term[index + operator.len()..].trim().trim_matches(|c| c == '"' || c == '\'').to_string(),
+    })
+}
+
+// Windows NT versions as reported by Win32_OperatingSystem.Version.
+fn describe_version(operator: &str, value: &str) -> String {
+    let prefix = value.trim_end_matches('%').trim_end_matches('.');
+    let name = match prefix {
+        "10" | "10.0" => "Windows 10",
+        "6.3" => "Windows 8.1",
+        "6.2" => "Windows 8",
+        "6.1" => "Windows 7",
+        "6.0" | "6" => "Windows Vista",
+        "5.2" => "Windows XP x64",
+        "5.1" => "Windows XP",
+        _ => return format!("version {} {}", operator.to_lowercase(), value),
+    };
+    // "10.%" only matches the 10.x family, which is every release since Windows 10.
+    match operator {
+        "LIKE" if prefix.starts_with("10") => format!("{}+", name),
+        "LIKE" | "=" => name.to_string(),
+        ">=" | ">" => format!("{}+", name),
+        "<" => format!("older than {}", name),
+        "<=" => format!("{} or older", name),
+        "<>" | "!=" | "NOT LIKE" => format!("not {}", name),
+        _ => format!("version {} {}", operator.to_lowercase(), value),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WmiFilter {
+    pub name: String,
+    pub description: String,
+    pub queries: Vec<WqlQuery>,
+}
+
+impl WmiFilter {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: String::new(),
+            queries: Vec::new(),
+        }
+    }
+
+    fn add_query(&mut self, query: &str) {
+        let (namespace, query) = match query.split_once(';') {
+            Some((namespace, query)) => (namespace.trim(), query.trim()),
+            None => ("root\\CIMv2", query.trim()),
+        };
+        self.queries.push(WqlQuery::parse(namespace, query));
+    }
+
+    pub fn parse(content: &str) -> Option<Self> {
+        Self::parse_lines(content, &mut HashSet::new())
+    }
+
+    // Like parse, adding the line numbers of the rows it read to "parsed".
+    pub fn parse_lines(content: &str, parsed: &mut HashSet<usize>) -> Option<Self> {
+        let mut filter: Option<WmiFilter> = None;
+
+        for (index, line) in content.lines().enumerate() {
+            if table::is_border(line) || line.trim().is_empty() {
+                continue;
+            }
+            if !table::is_row(line) {
+                let heading = line.trim().to_lowercase();
+                if filter.is_some() && heading != "wmi filtering" && heading != "queries" {
+                    break;
+                }
+                continue;
+            }
+            let row = table::split_row(line);
+            let key = row[0].to_lowercase();
+            let value = table::cell(&row, Some(1));
+            if key == "wmi filter name" || key == "wmi filter" {
+                parsed.insert(index);
+                if value.is_empty() || value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("<none>") {
+                    return None;
+                }
+                filter = Some(WmiFilter::new(&value));
+                continue;
+            }
+            let Some(current) = filter.as_mut() else {
+                continue;
+            };
+            if key == "description" {
+                current.description = value;
+            } else if key == "query" || key == "queries" || key == "wql" {
+                current.add_query(&value);
+            } else if key == "namespace" {
+                // "Namespace │ Query" table header
+            } else if value.to_lowercase().starts_with("select ") {
+                current.queries.push(WqlQuery::parse(&row[0], &value));
+            } else if key.starts_with("select ") {
+                current.add_query(&row[0]);
+            } else {
+                continue;
+            }
+            parsed.insert(index);
+        }
+
+        filter
+    }
+
+    // Readable targeting summary across every query, i.e. "workstations, Windows 10+".
+    pub fn targeting(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        for query in self.queries.iter() {
+            for part in query.summary() {
+                if !parts.contains(&part) {
+                    parts.push(part);
+                }
+            }
+        }
+        parts.join(", ")
+    }
+
+    fn field(&self, name: &str) -> Vec<String> {
+        match name.to_lowercase().as_str() {
+            "name" => vec!(self.name.clone()),
+            "description" => vec!(self.description.clone()),
+            "query" => self.queries.iter().map(|query| query.query.clone()).collect(),
+            "namespace" => self.queries.iter().map(|query| query.namespace.clone()).collect(),
+            "targeting" => vec!(self.targeting()),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_match(&self, field: &str, value: &str) -> bool {
+        self.field(field).iter().any(|current| value.is_empty() || matches_value(current, value))
+    }
+}
+
+impl fmt::Display for WmiFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WMI Filter: {} | Targets: {}", self.name, self.targeting())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wql_summary() {
+        let query = WqlQuery::parse("root\\CIMv2", "SELECT * FROM Win32_OperatingSystem WHERE Version LIKE \"10.%\" AND ProductType=\"1\"");
+        assert_eq!(query.class, "Win32_OperatingSystem");
+        assert_eq!(query.conditions.len(), 2);
+        assert_eq!(query.conditions[1], WqlCondition { property: "ProductType".to_string(), operator: "=".to_string(), value: "1".to_string() });
+        assert_eq!(query.summary().join(", "), "workstations, Windows 10+");
+
+        let query = WqlQuery::parse("root\\CIMv2", "select * from Win32_OperatingSystem where (ProductType <> 1) and Version >= '6.1'");
+        assert_eq!(query.summary().join(", "), "domain controllers and servers, Windows 7+");
+
+        let query = WqlQuery::parse("root\\CIMv2", "SELECT * FROM Win32_OperatingSystem WHERE ProductType=\"1\" OR ProductType=\"3\"");
+        assert_eq!(query.logic, WqlLogic::Any);
+        assert_eq!(query.summary().join(", "), "workstations and servers");
+
+        // AND and OR together, or OR across properties, can't be written as a list of targets.
+        let wql = "SELECT * FROM Win32_OperatingSystem WHERE ProductType=\"1\" AND Version LIKE \"10.%\" OR ProductType=\"3\"";
+        let query = WqlQuery::parse("root\\CIMv2", wql);
+        assert_eq!(query.logic, WqlLogic::Mixed);
+        assert_eq!(query.conditions.len(), 3);
+        assert_eq!(query.summary(), vec!(wql));
+        let wql = "SELECT * FROM Win32_OperatingSystem WHERE ProductType=\"1\" OR Version LIKE \"10.%\"";
+        assert_eq!(WqlQuery::parse("root\\CIMv2", wql).summary(), vec!(wql));
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter = WmiFilter::parse("\
+Details
+Domain │ labs.local
+WMI Filter Name │ Windows 10 Workstations
+Description │ Client hardening only
+Query │ root\\CIMv2; SELECT * FROM Win32_OperatingSystem WHERE Version LIKE \"10.%\" AND ProductType=\"1\"
+
+Links
+").unwrap();
+        assert_eq!(filter.name, "Windows 10 Workstations");
+        assert_eq!(filter.description, "Client hardening only");
+        assert_eq!(filter.queries[0].namespace, "root\\CIMv2");
+        assert_eq!(filter.targeting(), "workstations, Windows 10+");
+        assert!(filter.is_match("Targeting", "<workstations"));
+        assert!(WmiFilter::parse("WMI Filter Name │ <none>\n").is_none());
+    }
+}